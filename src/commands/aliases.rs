@@ -0,0 +1,152 @@
+use serenity::model::prelude::GuildId;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::prelude::Context;
+use crate::storage::{self, Change, Persistence, PersistenceContainer};
+use crate::structures::{Snippet, State};
+
+use super::{arg, audit, is_owner, respond_err, respond_ok, subcommand};
+use super::snippets::{global_denied, read_only, Failure};
+
+pub(super) async fn snippet_alias(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  match subcommand(interaction) {
+    Some("add") => add_alias(ctx, interaction).await,
+    Some("remove") => remove_alias(ctx, interaction).await,
+    _ => panic!("Invalid subcommand provided to command: {}", interaction.data.name)
+  }
+}
+
+async fn add_alias(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let (id, alias) = match (arg(interaction, "id"), arg(interaction, "alias")) {
+    (
+      CommandDataOptionValue::String(id),
+      CommandDataOptionValue::String(alias)
+    ) => (id, alias.trim().to_string()),
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  let guild = interaction.guild_id;
+  let owner = is_owner(ctx, interaction).await;
+
+  let result = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+
+    push_alias(state, &persistence, guild, owner, &id, &alias)
+  };
+
+  let (before, after) = match result {
+    Ok(change) => change,
+    Err((title, content)) => return respond_err(ctx, interaction, title, &content).await
+  };
+
+  let id = after.id.clone();
+  audit(ctx, interaction, Some(&id), Some(before), Some(after)).await;
+
+  let content = &format!("'{alias}' now refers to the snippet '{id}'");
+  respond_ok(ctx, interaction, "Alias successfully added", content).await
+}
+
+async fn remove_alias(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let alias = match arg(interaction, "alias") {
+    CommandDataOptionValue::String(alias) => alias,
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  let guild = interaction.guild_id;
+  let owner = is_owner(ctx, interaction).await;
+
+  let result = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+
+    drop_alias(state, &persistence, guild, owner, &alias)
+  };
+
+  let (before, after) = match result {
+    Ok(change) => change,
+    Err((title, content)) => return respond_err(ctx, interaction, title, &content).await
+  };
+
+  let id = after.id.clone();
+  audit(ctx, interaction, Some(&id), Some(before), Some(after)).await;
+
+  let content = &format!("'{alias}' no longer refers to the snippet '{id}'");
+  respond_ok(ctx, interaction, "Alias successfully removed", content).await
+}
+
+/// Adds `alias` to the snippet `id`, returning the snippet before and after.
+fn push_alias(
+  state: &mut State,
+  persistence: &Persistence,
+  guild: Option<GuildId>,
+  owner: bool,
+  id: &str,
+  alias: &str
+) -> Result<(Snippet, Snippet), Failure> {
+  let scope = match state.find_scope(guild, id) {
+    Some(None) if storage::is_read_only() => return Err(read_only(id)),
+    Some(None) if !owner => return Err(global_denied(id)),
+    Some(scope) => scope,
+    None => return Err(("Failed to add alias", format!("The snippet '{id}' does not exist")))
+  };
+
+  let snippet_id = state.scope(scope).iter()
+    .find(|s| s.matches(id))
+    .map(|s| s.id.clone())
+    .expect("Snippet was found without a scope");
+
+  if let Some(conflict) = state.find_conflict(scope, alias, &snippet_id) {
+    return Err(("Failed to add alias", format!("'{alias}' is already used by the snippet '{}'", conflict.id)))
+  }
+
+  let snippet = state.scope_mut(scope).iter_mut()
+    .find(|s| s.id == snippet_id)
+    .expect("Snippet was found without a scope");
+
+  if alias.is_empty() || snippet.matches(alias) {
+    return Err(("Failed to add alias", format!("'{alias}' already refers to the snippet '{}'", snippet.id)))
+  }
+
+  println!("Adding alias '{alias}' to snippet '{}'", snippet.id);
+
+  let before = snippet.snapshot();
+  snippet.aliases.push(alias.to_string());
+  persistence.mark(Change::Snippet { scope, id: snippet.id.clone() });
+
+  Ok((before, snippet.snapshot()))
+}
+
+/// Removes `alias` from the snippet it refers to, returning the snippet before
+/// and after.
+fn drop_alias(
+  state: &mut State,
+  persistence: &Persistence,
+  guild: Option<GuildId>,
+  owner: bool,
+  alias: &str
+) -> Result<(Snippet, Snippet), Failure> {
+  let scope = match state.find_scope(guild, alias) {
+    Some(None) if storage::is_read_only() => return Err(read_only(alias)),
+    Some(None) if !owner => return Err(global_denied(alias)),
+    Some(scope) => scope,
+    None => return Err(("Failed to remove alias", format!("No snippet has the alias '{alias}'")))
+  };
+
+  let snippet = state.scope_mut(scope).iter_mut()
+    .find(|s| s.matches(alias))
+    .expect("Snippet was found without a scope");
+
+  if snippet.id == alias {
+    return Err(("Failed to remove alias", format!("'{alias}' is the id of the snippet, not an alias")))
+  }
+
+  println!("Removing alias '{alias}' from snippet '{}'", snippet.id);
+
+  let before = snippet.snapshot();
+  snippet.aliases.retain(|a| a != alias);
+  persistence.mark(Change::Snippet { scope, id: snippet.id.clone() });
+
+  Ok((before, snippet.snapshot()))
+}
@@ -0,0 +1,164 @@
+use chrono::{Duration, NaiveDate};
+use serenity::builder::CreateEmbed;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::Timestamp;
+use serenity::prelude::Context;
+use crate::storage::{AuditFilter, StorageContainer};
+use crate::structures::{AuditEntry, Snippet};
+
+use super::{arg_opt, respond_embed, respond_err};
+
+const DEFAULT_LIMIT: usize = 10;
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub(super) async fn audit(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let actor = match arg_opt(interaction, "user") {
+    Some(CommandDataOptionValue::User(user, _)) => Some(user.id),
+    _ => None
+  };
+
+  let snippet = match arg_opt(interaction, "id") {
+    Some(CommandDataOptionValue::String(id)) => Some(id),
+    _ => None
+  };
+
+  let limit = match arg_opt(interaction, "limit") {
+    Some(CommandDataOptionValue::Integer(limit)) => limit.clamp(1, 25) as usize,
+    _ => DEFAULT_LIMIT
+  };
+
+  let (since, until) = match (parse_date(interaction, "since", 0), parse_date(interaction, "until", 1)) {
+    (Ok(since), Ok(until)) => (since, until),
+    (Err(e), _) | (_, Err(e)) => {
+      return respond_err(ctx, interaction, "Invalid date provided", &e).await
+    }
+  };
+
+  let filter = AuditFilter {
+    guild: interaction.guild_id,
+    actor,
+    snippet,
+    since,
+    until,
+    limit
+  };
+
+  let storage = {
+    let data = ctx.data.read().await;
+    data.get::<StorageContainer>().expect("Failed to get storage").clone()
+  };
+
+  let result = tokio::task::spawn_blocking(move || storage.query_audit(&filter))
+    .await
+    .expect("Failed to join storage task");
+
+  match result {
+    Ok(entries) => {
+      let mut embed = CreateEmbed::default();
+      embed.title(format!("Audit log ({} entries)", entries.len()))
+        .colour(super::ACCENT_COLOUR);
+
+      if entries.is_empty() {
+        embed.description("No changes match these filters");
+      }
+
+      for entry in &entries {
+        let name = match &entry.snippet {
+          Some(id) => format!("{} `{}`", entry.command, id),
+          None => entry.command.clone()
+        };
+
+        let value = format!("<@{}> <t:{}:f>\n{}", entry.actor, entry.timestamp.unix_timestamp(), describe(entry));
+        embed.field(name, value, false);
+      }
+
+      respond_embed(ctx, interaction, &embed, false).await
+    },
+    Err(e) => {
+      let content = &format!("Unable to read the audit log: {e}");
+      respond_err(ctx, interaction, "Failed to query audit log", content).await
+    }
+  }
+}
+
+/// Parses the `YYYY-MM-DD` date option `name` as the start of that day, plus
+/// `offset_days` days.
+fn parse_date(interaction: &ApplicationCommandInteraction, name: &'static str, offset_days: i64) -> Result<Option<Timestamp>, String> {
+  match arg_opt(interaction, name) {
+    Some(CommandDataOptionValue::String(date)) => {
+      let date = NaiveDate::parse_from_str(&date, DATE_FORMAT)
+        .map_err(|e| format!("The {name} date '{date}' is not a valid YYYY-MM-DD date: {e}"))?;
+
+      let start = (date + Duration::days(offset_days)).and_hms(0, 0, 0);
+      Timestamp::from_unix_timestamp(start.timestamp())
+        .map(Some)
+        .map_err(|e| format!("The {name} date is out of range: {e}"))
+    },
+    _ => Ok(None)
+  }
+}
+
+fn describe(entry: &AuditEntry) -> String {
+  if let Some(details) = &entry.details {
+    return details.chars().take(900).collect()
+  }
+
+  let description = match (&entry.before, &entry.after) {
+    (None, Some(after)) => format!("Created '{}'", after.title),
+    (Some(before), None) => format!("Removed '{}'", before.title),
+    (Some(before), Some(after)) => {
+      let mut changes = Vec::new();
+
+      if before.title != after.title {
+        changes.push(format!("Title '{}' → '{}'", before.title, after.title));
+      }
+
+      if before.content != after.content {
+        changes.push(format!("Content changed ({} → {} characters)", before.content.chars().count(), after.content.chars().count()));
+      }
+
+      if before.aliases != after.aliases {
+        changes.push(format!("Aliases [{}] → [{}]", before.aliases.join(", "), after.aliases.join(", ")));
+      }
+
+      if before.category != after.category {
+        let category = |c: &Option<String>| c.clone().unwrap_or_else(|| "none".to_string());
+        changes.push(format!("Category '{}' → '{}'", category(&before.category), category(&after.category)));
+      }
+
+      if before.tags != after.tags {
+        changes.push(format!("Tags [{}] → [{}]", before.tags.join(", "), after.tags.join(", ")));
+      }
+
+      let appearance_changed = before.fields != after.fields
+        || before.image != after.image
+        || before.thumbnail != after.thumbnail
+        || before.colour != after.colour
+        || before.footer != after.footer
+        || before.links != after.links;
+
+      if appearance_changed {
+        changes.push("Embed appearance changed".to_string());
+      }
+
+      if before.platforms != after.platforms {
+        let platforms = |s: &Snippet| s.platforms.keys().map(|p| p.label()).collect::<Vec<&str>>().join(", ");
+        changes.push(format!("OS sections changed, [{}] → [{}]", platforms(before), platforms(after)));
+      }
+
+      if before.variants != after.variants {
+        let locales = |s: &Snippet| s.variants.keys().cloned().collect::<Vec<String>>().join(", ");
+        changes.push(format!("Translations changed, [{}] → [{}]", locales(before), locales(after)));
+      }
+
+      if changes.is_empty() {
+        "No visible changes".to_string()
+      } else {
+        changes.join("\n")
+      }
+    },
+    (None, None) => "Replaced the state".to_string()
+  };
+
+  description.chars().take(900).collect()
+}
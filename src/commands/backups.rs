@@ -0,0 +1,96 @@
+use serenity::builder::CreateEmbed;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::prelude::Context;
+use crate::storage::StorageContainer;
+use crate::structures::State;
+
+use super::{arg, audit, is_owner, respond_embed, respond_err, respond_ok};
+
+pub(super) async fn backups(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  if !is_owner(ctx, interaction).await {
+    return respond_not_owner(ctx, interaction).await
+  }
+
+  let storage = {
+    let data = ctx.data.read().await;
+    data.get::<StorageContainer>().expect("Failed to get storage").clone()
+  };
+
+  let result = tokio::task::spawn_blocking(move || storage.list_backups())
+    .await
+    .expect("Failed to join storage task");
+
+  match result {
+    Ok(backups) => {
+      let description = if backups.is_empty() {
+        "There are no backups".to_string()
+      } else {
+        backups.iter()
+          .map(|b| format!("`{}`", b.name))
+          .collect::<Vec<String>>()
+          .join("\n")
+      };
+
+      let mut embed = CreateEmbed::default();
+      embed.title(format!("Backups ({})", backups.len()))
+        .description(description)
+        .colour(super::ACCENT_COLOUR);
+
+      respond_embed(ctx, interaction, &embed, false).await;
+    },
+    Err(e) => {
+      let content = &format!("Unable to list backups: {e}");
+      respond_err(ctx, interaction, "Failed to list backups", content).await
+    }
+  }
+}
+
+pub(super) async fn restore_backup(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  if !is_owner(ctx, interaction).await {
+    return respond_not_owner(ctx, interaction).await
+  }
+
+  match arg(interaction, "name") {
+    CommandDataOptionValue::String(name) => {
+      println!("Restoring backup '{name}'");
+
+      let storage = {
+        let data = ctx.data.read().await;
+        data.get::<StorageContainer>().expect("Failed to get storage").clone()
+      };
+
+      let result = {
+        let name = name.clone();
+        tokio::task::spawn_blocking(move || storage.restore_backup(&name))
+          .await
+          .expect("Failed to join storage task")
+      };
+
+      match result {
+        Ok(state) => {
+          let count = state.snippet_count();
+
+          {
+            let mut data = ctx.data.write().await;
+            data.insert::<State>(state);
+          }
+
+          audit(ctx, interaction, None, None, None).await;
+
+          let content = &format!("Restored '{name}' with {count} snippets");
+          respond_ok(ctx, interaction, "Backup successfully restored", content).await
+        },
+        Err(e) => {
+          let content = &format!("Unable to restore '{name}': {e}");
+          respond_err(ctx, interaction, "Failed to restore backup", content).await
+        }
+      }
+    },
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  }
+}
+
+async fn respond_not_owner(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let content = "Only the bot owner may manage state backups";
+  respond_err(ctx, interaction, "Insufficient permissions", content).await
+}
@@ -0,0 +1,407 @@
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::prelude::{GuildId, InteractionId};
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::prelude::interaction::autocomplete::AutocompleteInteraction;
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::Timestamp;
+use serenity::prelude::{Context, TypeMapKey};
+use std::collections::HashMap;
+use crate::search;
+use crate::template::Variables;
+use crate::structures::{Embeddable, Snippet, State, Usage, UsageCounts, UsageSource};
+
+use super::{arg, arg_opt, platforms, record_usage, subcommand};
+
+/// The number of results shown on each page.
+const PAGE_SIZE: usize = 5;
+/// The number of results kept, which select menus cannot exceed.
+const MAX_RESULTS: usize = 25;
+/// The longest query kept in a button's custom id, which is limited to 100
+/// characters in total.
+const MAX_QUERY_LENGTH: usize = 80;
+/// The number of snippets shown on each page of the list.
+const LIST_PAGE_SIZE: usize = 15;
+/// How long the filters of a snippet list are remembered for its page
+/// buttons, in seconds.
+const LIST_LIFETIME: i64 = 24 * 60 * 60;
+
+/// The filters of `/snippets list`.
+#[derive(Clone, Default)]
+pub struct ListFilter {
+  category: Option<String>,
+  tag: Option<String>
+}
+
+/// The filters of the snippet lists that were run with any, by the id of the
+/// command, which the page buttons refer to as they are too long to fit in a
+/// custom id.
+pub struct ListFilters;
+
+impl TypeMapKey for ListFilters {
+  type Value = HashMap<InteractionId, ListFilter>;
+}
+
+pub(super) async fn snippets(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  match subcommand(interaction) {
+    Some("search") => search(ctx, interaction).await,
+    Some("list") => list(ctx, interaction).await,
+    Some("sync") => super::sync::sync(ctx, interaction).await,
+    _ => panic!("Invalid subcommand provided to command: {}", interaction.data.name)
+  }
+}
+
+async fn search(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let query = match arg(interaction, "query") {
+    CommandDataOptionValue::String(query) => query.chars().take(MAX_QUERY_LENGTH).collect::<String>(),
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  let (embed, components) = search_page(ctx, interaction.guild_id, &query, 0).await;
+
+  let result = interaction.create_followup_message(ctx, |r| r
+    .add_embed(embed)
+    .set_components(components)
+  ).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to interaction '{}': {:#?}", interaction.data.name, e)
+  }
+}
+
+/// Shows another page of search results, from the buttons below them.
+pub(super) async fn change_page(ctx: &Context, interaction: &MessageComponentInteraction) {
+  let mut parts = interaction.data.custom_id.splitn(3, ':').skip(1);
+  let page = parts.next().and_then(|p| p.parse().ok()).unwrap_or_default();
+  let query = parts.next().unwrap_or_default();
+
+  let (embed, components) = search_page(ctx, interaction.guild_id, query, page).await;
+
+  let result = interaction.create_interaction_response(ctx, |r| r
+    .kind(InteractionResponseType::UpdateMessage)
+    .interaction_response_data(|d| d
+      .set_embed(embed)
+      .set_components(components)
+    )
+  ).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to component '{}': {:#?}", interaction.data.custom_id, e)
+  }
+}
+
+/// Posts the snippet picked from the select menu below the search results.
+pub(super) async fn post_snippet(ctx: &Context, interaction: &MessageComponentInteraction) {
+  let snippet = match interaction.data.values.first() {
+    Some(id) => {
+      let data = ctx.data.read().await;
+      let state = data.get::<State>().expect("Failed to get state");
+      state.get_snippet(interaction.guild_id, id).cloned()
+    },
+    None => None
+  };
+
+  let rendered = match snippet {
+    Some(snippet) => {
+      let platform = platforms::user_platform(ctx, interaction.guild_id, interaction.user.id).await;
+      let variables = Variables::new(interaction.user.id, interaction.channel_id, Vec::new());
+
+      platforms::render_for_platform(ctx, interaction.id.0, &snippet, &interaction.locale, platform, variables).await
+        .map_err(|e| e.to_string())
+    },
+    None => Err("This snippet no longer exists".to_string())
+  };
+
+  let id = rendered.as_ref().ok().map(|(s, _)| s.id.clone());
+
+  let result = interaction.create_interaction_response(ctx, |r| r
+    .kind(InteractionResponseType::ChannelMessageWithSource)
+    .interaction_response_data(|d| match rendered {
+      Ok((snippet, components)) => d.add_embed(snippet.embed()).set_components(components),
+      Err(e) => d.content(e).ephemeral(true)
+    })
+  ).await;
+
+  match (result, id) {
+    (Ok(_), Some(id)) => {
+      let usage = Usage::new(interaction.user.id, interaction.guild_id, interaction.channel_id, id, UsageSource::Search);
+      record_usage(ctx, usage).await
+    },
+    (Ok(_), None) => (),
+    (Err(e), _) => println!("Failed to respond to component '{}': {:#?}", interaction.data.custom_id, e)
+  }
+}
+
+async fn search_page(ctx: &Context, guild: Option<GuildId>, query: &str, page: usize) -> (CreateEmbed, CreateComponents) {
+  let results: Vec<Snippet> = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+
+    let uses = data.get::<UsageCounts>().and_then(|c| c.get(&guild));
+
+    search::fuzzy(state.snippets(guild), query, uses).into_iter()
+      .take(MAX_RESULTS)
+      .cloned()
+      .collect()
+  };
+
+  let pages = ((results.len() + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+  let page = page.min(pages - 1);
+  let shown = results.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE);
+
+  let mut embed = CreateEmbed::default();
+  embed.title(format!("Snippets matching '{query}'"))
+    .colour(super::ACCENT_COLOUR)
+    .footer(|f| f.text(format!("Page {} of {pages} · {} results", page + 1, results.len())));
+
+  if results.is_empty() {
+    embed.description("No snippets match this search");
+  }
+
+  for snippet in shown.clone() {
+    embed.field(truncate(&format!("{} (`{}`)", snippet.title, snippet.id), 256), preview(&snippet.content), false);
+  }
+
+  let mut components = CreateComponents::default();
+
+  if results.is_empty() {
+    return (embed, components)
+  }
+
+  components.create_action_row(|row| row
+    .create_select_menu(|menu| menu
+      .custom_id("snippets-post")
+      .placeholder("Post a snippet in this channel")
+      .options(|options| {
+        for snippet in shown {
+          options.create_option(|o| o
+            .label(truncate(&snippet.title, 100))
+            .value(&snippet.id)
+            .description(truncate(&snippet.id, 100))
+          );
+        }
+
+        options
+      })
+    )
+  );
+
+  page_buttons(&mut components, page, pages, |page| format!("snippets-search:{page}:{query}"));
+
+  (embed, components)
+}
+
+async fn list(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let filter = ListFilter {
+    category: match arg_opt(interaction, "category") {
+      Some(CommandDataOptionValue::String(category)) => Some(category),
+      _ => None
+    },
+    tag: match arg_opt(interaction, "tag") {
+      Some(CommandDataOptionValue::String(tag)) => Some(tag),
+      _ => None
+    }
+  };
+
+  let key = if filter.category.is_some() || filter.tag.is_some() {
+    let mut data = ctx.data.write().await;
+    let filters = data.get_mut::<ListFilters>().expect("Failed to get list filters");
+    let now = Timestamp::now().unix_timestamp();
+
+    filters.retain(|id, _| now - id.created_at().unix_timestamp() < LIST_LIFETIME);
+    filters.insert(interaction.id, filter.clone());
+
+    interaction.id.to_string()
+  } else {
+    String::new()
+  };
+
+  let (embed, components) = list_page(ctx, interaction.guild_id, &filter, &key, 0).await;
+
+  let result = interaction.create_followup_message(ctx, |r| r
+    .add_embed(embed)
+    .set_components(components)
+  ).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to interaction '{}': {:#?}", interaction.data.name, e)
+  }
+}
+
+/// Shows another page of the snippet list, from the buttons below it.
+pub(super) async fn change_list_page(ctx: &Context, interaction: &MessageComponentInteraction) {
+  let mut parts = interaction.data.custom_id.splitn(3, ':').skip(1);
+  let page = parts.next().and_then(|p| p.parse().ok()).unwrap_or_default();
+  let key = parts.next().unwrap_or_default();
+
+  let filter = match key.parse().map(InteractionId) {
+    Ok(id) => {
+      let data = ctx.data.read().await;
+      data.get::<ListFilters>().and_then(|f| f.get(&id)).cloned()
+    },
+    Err(_) => Some(ListFilter::default())
+  };
+
+  let result = match filter {
+    Some(filter) => {
+      let (embed, components) = list_page(ctx, interaction.guild_id, &filter, key, page).await;
+
+      interaction.create_interaction_response(ctx, |r| r
+        .kind(InteractionResponseType::UpdateMessage)
+        .interaction_response_data(|d| d
+          .set_embed(embed)
+          .set_components(components)
+        )
+      ).await
+    },
+    None => {
+      interaction.create_interaction_response(ctx, |r| r
+        .kind(InteractionResponseType::ChannelMessageWithSource)
+        .interaction_response_data(|d| d
+          .content("This list has expired, use `/snippets list` again to browse it")
+          .ephemeral(true)
+        )
+      ).await
+    }
+  };
+
+  if let Err(e) = result {
+    println!("Failed to respond to component '{}': {:#?}", interaction.data.custom_id, e)
+  }
+}
+
+/// Suggests the categories or tags used by the snippets visible here.
+pub(super) async fn autocomplete_label(ctx: &Context, interaction: &AutocompleteInteraction, option: &str, query: &str) {
+  let query = query.to_lowercase();
+
+  let labels: Vec<String> = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+
+    let mut labels: Vec<String> = Vec::new();
+    for snippet in state.snippets(interaction.guild_id) {
+      let values = match option {
+        "category" => snippet.category.iter().cloned().collect(),
+        _ => snippet.tags.clone()
+      };
+
+      for value in values {
+        if value.to_lowercase().contains(&query) && !labels.iter().any(|l| l.eq_ignore_ascii_case(&value)) {
+          labels.push(value);
+        }
+      }
+    }
+
+    labels.sort_by_key(|l| l.to_lowercase());
+    labels
+  };
+
+  let result = interaction.create_autocomplete_response(ctx, |r| {
+    for label in labels.into_iter().take(25) {
+      r.add_string_choice(truncate(&label, 100), label);
+    }
+
+    r
+  }).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to autocomplete for '{}': {:#?}", interaction.data.name, e)
+  }
+}
+
+/// Renders a page of the snippets matching `filter`, where `key` refers the
+/// page buttons to the filter.
+async fn list_page(ctx: &Context, guild: Option<GuildId>, filter: &ListFilter, key: &str, page: usize) -> (CreateEmbed, CreateComponents) {
+  let mut snippets: Vec<Snippet> = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+
+    state.snippets(guild).into_iter()
+      .filter(|s| s.is_listed(filter.category.as_deref(), filter.tag.as_deref()))
+      .cloned()
+      .collect()
+  };
+
+  // Uncategorised snippets are listed last
+  snippets.sort_by_key(|s| (s.category.is_none(), s.category.as_ref().map(|c| c.to_lowercase()), s.id.clone()));
+
+  let pages = ((snippets.len() + LIST_PAGE_SIZE - 1) / LIST_PAGE_SIZE).max(1);
+  let page = page.min(pages - 1);
+
+  let title = match (&filter.category, &filter.tag) {
+    (Some(category), Some(tag)) => format!("{category} snippets tagged '{tag}'"),
+    (Some(category), None) => format!("{category} snippets"),
+    (None, Some(tag)) => format!("Snippets tagged '{tag}'"),
+    (None, None) => "Snippets".to_string()
+  };
+
+  let description = if snippets.is_empty() {
+    "No snippets match these filters".to_string()
+  } else {
+    snippets.iter()
+      .skip(page * LIST_PAGE_SIZE)
+      .take(LIST_PAGE_SIZE)
+      .map(|s| {
+        let mut line = format!("`{}` **{}**", s.id, s.title);
+
+        if let Some(category) = &s.category {
+          line.push_str(&format!(" · *{category}*"));
+        }
+
+        for tag in &s.tags {
+          line.push_str(&format!(" #{tag}"));
+        }
+
+        truncate(&line, 250)
+      })
+      .collect::<Vec<String>>()
+      .join("\n")
+  };
+
+  let mut embed = CreateEmbed::default();
+  embed.title(truncate(&title, 256))
+    .description(description)
+    .colour(super::ACCENT_COLOUR)
+    .footer(|f| f.text(format!("Page {} of {pages} · {} snippets", page + 1, snippets.len())));
+
+  let mut components = CreateComponents::default();
+  page_buttons(&mut components, page, pages, |page| format!("snippets-list:{page}:{key}"));
+
+  (embed, components)
+}
+
+/// Adds previous and next buttons if there is more than one page, where
+/// `custom_id` gives the id of the button that shows a page.
+fn page_buttons(components: &mut CreateComponents, page: usize, pages: usize, custom_id: impl Fn(usize) -> String) {
+  if pages > 1 {
+    components.create_action_row(|row| row
+      .create_button(|b| b
+        .custom_id(custom_id(page.saturating_sub(1)))
+        .label("Previous")
+        .style(ButtonStyle::Secondary)
+        .disabled(page == 0)
+      )
+      .create_button(|b| b
+        .custom_id(custom_id(page + 1))
+        .label("Next")
+        .style(ButtonStyle::Secondary)
+        .disabled(page + 1 >= pages)
+      )
+    );
+  }
+}
+
+fn preview(content: &str) -> String {
+  let line = content.lines().find(|l| !l.trim().is_empty()).unwrap_or("*No content*");
+
+  if line.chars().count() > 100 || content.lines().count() > 1 {
+    format!("{}…", truncate(line, 99))
+  } else {
+    line.to_string()
+  }
+}
+
+pub(super) fn truncate(text: &str, length: usize) -> String {
+  text.chars().take(length).collect()
+}
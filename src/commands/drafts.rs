@@ -0,0 +1,340 @@
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::prelude::GuildId;
+use serenity::model::Timestamp;
+use serenity::prelude::Context;
+use crate::formatting;
+use crate::storage::{Change, PersistenceContainer};
+use crate::structures::{AuditEntry, Draft, Snippet, SnippetAction, State};
+
+use super::{arg, arg_opt, audit_settings, is_owner, permissions, record_audit, respond_embed, respond_err, respond_ok};
+use super::snippets::Failure;
+
+/// The longest diff shown in a review, leaving room for the code block.
+const MAX_DIFF_LENGTH: usize = 3900;
+
+pub(super) async fn propose_snippet(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let id = match arg(interaction, "id") {
+    CommandDataOptionValue::String(id) => id,
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  let title = match arg_opt(interaction, "title") {
+    Some(CommandDataOptionValue::String(title)) => Some(title),
+    _ => None
+  };
+
+  let content = match arg_opt(interaction, "content") {
+    Some(CommandDataOptionValue::String(content)) => Some(content.replace(r#"\n"#, "\n")),
+    _ => None
+  };
+
+  match save_proposal(ctx, interaction, &id, title, content).await {
+    Ok(embed) => respond_embed(ctx, interaction, &embed, false).await,
+    Err((title, content)) => respond_err(ctx, interaction, title, &content).await
+  }
+}
+
+/// Posts a draft of the snippet `id` with `title` and `content` to the review
+/// channel, keeping the current title or content of an existing snippet when
+/// either is `None`.
+pub(super) async fn save_proposal(
+  ctx: &Context,
+  interaction: &ApplicationCommandInteraction,
+  id: &str,
+  title: Option<String>,
+  content: Option<String>
+) -> Result<CreateEmbed, Failure> {
+  let fail = |content: String| ("Failed to propose snippet", content);
+
+  let guild = interaction.guild_id
+    .ok_or_else(|| fail("Snippets can only be proposed in a server".to_string()))?;
+
+  let (channel, base) = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+
+    let channel = state.settings(guild).review_channel
+      .ok_or_else(|| fail("This server has no review channel, ask a manager to set one with /snippet-review".to_string()))?;
+
+    let base = state.scope(Some(guild)).iter()
+      .find(|s| s.matches(id))
+      .map(Snippet::snapshot);
+
+    if base.is_none() && state.find_scope(Some(guild), id).is_some() {
+      return Err(fail(format!("The snippet '{id}' is shared with every server, only the bot owner may change it")))
+    }
+
+    (channel, base)
+  };
+
+  let snippet = match (&base, title, content) {
+    (Some(base), title, content) => Snippet {
+      title: title.unwrap_or_else(|| base.title.clone()),
+      content: content.unwrap_or_else(|| base.content.clone()),
+      ..base.clone()
+    },
+    (None, Some(title), Some(content)) => Snippet::new(id.to_string(), title, content),
+    (None, _, _) => return Err(fail(format!("The snippet '{id}' does not exist yet, so a draft needs both a title and content")))
+  };
+
+  if base.as_ref() == Some(&snippet) {
+    return Err(fail("The draft does not change the snippet".to_string()))
+  }
+
+  snippet.validate().map_err(fail)?;
+
+  let mut draft = Draft {
+    id: interaction.id.0,
+    author: interaction.user.id,
+    timestamp: Timestamp::now(),
+    snippet,
+    base,
+    message: None
+  };
+
+  let message = channel.send_message(ctx, |m| m
+    .set_embed(review_embed(&draft))
+    .set_components(review_buttons(draft.id))
+  ).await.map_err(|e| fail(format!("Unable to post the draft in <#{channel}>: {e}")))?;
+
+  draft.message = Some(message.id);
+
+  {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+
+    println!("Draft of snippet '{}' proposed in guild {guild}", draft.snippet.id);
+
+    state.drafts_mut(guild).push(draft.clone());
+    persistence.mark(Change::Draft { guild, id: draft.id });
+  }
+
+  let mut embed = CreateEmbed::default();
+  embed.title("Draft submitted for review")
+    .description(format!("Your draft of '{}' was posted for review in {}", draft.snippet.id, message.link()))
+    .colour(super::OK_COLOUR);
+
+  Ok(embed)
+}
+
+/// Approves or rejects the draft whose review message the button is on,
+/// applying it to the guild's snippets if approved.
+pub(super) async fn review_draft(ctx: &Context, interaction: &MessageComponentInteraction) {
+  let (kind, id) = interaction.data.custom_id.split_once(':')
+    .and_then(|(kind, id)| Some((kind, id.parse::<u64>().ok()?)))
+    .unwrap_or_else(|| panic!("Invalid custom id provided to component: {}", interaction.data.custom_id));
+
+  let approve = kind == "draft-approve";
+  let guild = interaction.guild_id.expect("Drafts are only reviewed in servers");
+
+  let draft = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+    state.drafts(guild).iter().find(|d| d.id == id).cloned()
+  };
+
+  let draft = match draft {
+    Some(draft) => draft,
+    None => return respond_ephemeral(ctx, interaction, "This draft was already reviewed").await
+  };
+
+  let action = match draft.base {
+    Some(_) => SnippetAction::Edit,
+    None => SnippetAction::Create
+  };
+
+  if let Some(denial) = permissions::review_denied(ctx, guild, interaction.user.id, interaction.member.as_ref(), action).await {
+    return respond_ephemeral(ctx, interaction, &denial).await
+  }
+
+  if approve && draft.author == interaction.user.id {
+    return respond_ephemeral(ctx, interaction, "You cannot approve your own draft, another reviewer has to").await
+  }
+
+  let result = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+
+    let applied = match state.drafts(guild).iter().any(|d| d.id == id) {
+      true if approve => apply_draft(state, guild, &draft).map(Some),
+      true => Ok(None),
+      false => Err("This draft was already reviewed".to_string())
+    };
+
+    if applied.is_ok() {
+      println!("Draft of snippet '{}' {} in guild {guild}", draft.snippet.id, if approve { "approved" } else { "rejected" });
+
+      state.drafts_mut(guild).retain(|d| d.id != id);
+      persistence.mark(Change::Draft { guild, id });
+    }
+
+    if let Ok(Some(_)) = applied {
+      persistence.mark(Change::Snippet { scope: Some(guild), id: draft.snippet.id.clone() });
+    }
+
+    applied
+  };
+
+  match result {
+    Ok(applied) => {
+      if let Some((before, after)) = applied {
+        let entry = AuditEntry {
+          timestamp: Timestamp::now(),
+          actor: interaction.user.id,
+          guild: Some(guild),
+          command: "approve-draft".to_string(),
+          snippet: Some(after.id.clone()),
+          before,
+          after: Some(after),
+          details: None
+        };
+
+        record_audit(ctx, entry).await;
+      }
+
+      let mut embed = review_embed(&draft);
+      match approve {
+        true => embed.colour(super::OK_COLOUR).footer(|f| f.text(format!("Approved by {}", interaction.user.name))),
+        false => embed.colour(super::ERROR_COLOUR).footer(|f| f.text(format!("Rejected by {}", interaction.user.name)))
+      };
+
+      let result = interaction.create_interaction_response(ctx, |r| r
+        .kind(InteractionResponseType::UpdateMessage)
+        .interaction_response_data(|d| d
+          .set_embed(embed)
+          .set_components(CreateComponents::default())
+        )
+      ).await;
+
+      if let Err(e) = result {
+        println!("Failed to respond to component '{}': {:#?}", interaction.data.custom_id, e)
+      }
+    },
+    Err(e) => respond_ephemeral(ctx, interaction, &e).await
+  }
+}
+
+/// Applies an approved `draft` to the snippets of `guild`, returning the
+/// snippet before and after, or why it can no longer be applied.
+fn apply_draft(state: &mut State, guild: GuildId, draft: &Draft) -> Result<(Option<Snippet>, Snippet), String> {
+  let id = &draft.snippet.id;
+
+  if draft.base.is_none() {
+    if let Some(conflict) = state.find_conflict(Some(guild), id, "") {
+      return Err(format!("'{id}' was taken by the snippet '{}' after this draft was proposed", conflict.id))
+    }
+
+    state.scope_mut(Some(guild)).push(draft.snippet.clone());
+    return Ok((None, draft.snippet.clone()))
+  }
+
+  let current = state.scope_mut(Some(guild)).iter_mut()
+    .find(|s| &s.id == id)
+    .ok_or_else(|| format!("The snippet '{id}' was removed after this draft was proposed"))?;
+
+  match &draft.base {
+    Some(base) if current.title == base.title && current.content == base.content => {
+      let before = current.snapshot();
+      current.revise(draft.author, draft.snippet.title.clone(), draft.snippet.content.clone());
+      Ok((Some(before), current.snapshot()))
+    },
+    _ => Err(format!("The snippet '{id}' was changed after this draft was proposed, reject it and propose the change again"))
+  }
+}
+
+fn review_embed(draft: &Draft) -> CreateEmbed {
+  let (heading, title, content) = match &draft.base {
+    Some(base) => ("Proposed edit to", base.title.as_str(), base.content.as_str()),
+    None => ("Proposed snippet", "", "")
+  };
+
+  let diff: String = formatting::diff(content, &draft.snippet.content)
+    .replace("```", "`\u{200b}`\u{200b}`")
+    .chars()
+    .take(MAX_DIFF_LENGTH)
+    .collect();
+
+  let mut embed = CreateEmbed::default();
+  embed.title(format!("{heading} '{}'", draft.snippet.id))
+    .description(format!("```diff\n{diff}\n```"))
+    .colour(super::ACCENT_COLOUR)
+    .timestamp(draft.timestamp);
+
+  match draft.base {
+    Some(_) if title != draft.snippet.title => embed.field("Title", format!("{title} → {}", draft.snippet.title), false),
+    Some(_) => embed.field("Title", title, false),
+    None => embed.field("Title", &draft.snippet.title, false)
+  };
+
+  embed.field("Proposed by", format!("<@{}>", draft.author), false);
+  embed
+}
+
+fn review_buttons(id: u64) -> CreateComponents {
+  CreateComponents::default()
+    .create_action_row(|row| row
+      .create_button(|b| b
+        .custom_id(format!("draft-approve:{id}"))
+        .label("Approve")
+        .style(ButtonStyle::Success)
+      )
+      .create_button(|b| b
+        .custom_id(format!("draft-reject:{id}"))
+        .label("Reject")
+        .style(ButtonStyle::Danger)
+      )
+    )
+    .clone()
+}
+
+async fn respond_ephemeral(ctx: &Context, interaction: &MessageComponentInteraction, content: &str) {
+  let result = interaction.create_interaction_response(ctx, |r| r
+    .kind(InteractionResponseType::ChannelMessageWithSource)
+    .interaction_response_data(|d| d.content(content).ephemeral(true))
+  ).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to component '{}': {:#?}", interaction.data.custom_id, e)
+  }
+}
+
+pub(super) async fn snippet_review(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let guild = match interaction.guild_id {
+    Some(guild) => guild,
+    None => return respond_err(ctx, interaction, "Failed to set review channel", "Drafts can only be reviewed in a server").await
+  };
+
+  let manager = interaction.member.as_ref()
+    .and_then(|m| m.permissions)
+    .is_some_and(|p| p.manage_guild());
+
+  if !manager && !is_owner(ctx, interaction).await {
+    let content = "Only members who can manage the server may set the review channel";
+    return respond_err(ctx, interaction, "Insufficient permissions", content).await
+  }
+
+  let channel = match arg(interaction, "channel") {
+    CommandDataOptionValue::Channel(channel) => channel.id,
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+
+    state.settings_mut(guild).review_channel = Some(channel);
+    persistence.mark(Change::Settings { guild });
+  }
+
+  audit_settings(ctx, interaction, format!("Set the review channel to <#{channel}>")).await;
+
+  let content = &format!("Drafts will now be posted in <#{channel}> for review");
+  respond_ok(ctx, interaction, "Review channel successfully set", content).await
+}
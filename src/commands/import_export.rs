@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{from_slice, to_vec_pretty};
+use serenity::builder::CreateEmbed;
+use serenity::model::channel::AttachmentType;
+use serenity::model::prelude::{GuildId, UserId};
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::prelude::Context;
+use serenity::utils::Colour;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use crate::storage::{self, Change, PersistenceContainer};
+use crate::structures::{Snippet, State};
+
+use super::{arg, arg_opt, audit, is_owner, list_ids, respond_embed, respond_err};
+
+/// The file format used by `export-snippets` and `import-snippets`.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum SnippetFile {
+  Snippets { snippets: Vec<Snippet> },
+  List(Vec<Snippet>)
+}
+
+#[derive(PartialEq, Eq)]
+enum ImportMode {
+  Merge,
+  Replace,
+  DryRun
+}
+
+/// A snippet's id with its state before and after a change, for auditing.
+pub(super) type ChangeRecord = (String, Option<Snippet>, Option<Snippet>);
+
+/// The changes an import would make to a scope.
+#[derive(Default)]
+pub(super) struct ImportPlan {
+  added: Vec<Snippet>,
+  changed: Vec<Snippet>,
+  removed: Vec<String>,
+  /// Ids that appear more than once in the file, or whose id or aliases are
+  /// already used by another snippet, which are never applied.
+  conflicting: Vec<String>,
+  /// Ids of snippets that exceed Discord's limits, which are never applied.
+  invalid: Vec<String>,
+  unchanged: usize
+}
+
+impl ImportPlan {
+  pub(super) fn new(existing: &[Snippet], incoming: Vec<Snippet>, replace: bool) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+
+    let mut seen = HashSet::new();
+    let duplicates: HashSet<String> = incoming.iter()
+      .filter(|s| !seen.insert(s.id.clone()))
+      .map(|s| s.id.clone())
+      .collect();
+
+    // The snippet each id and alias belongs to once the import is applied
+    let mut names: HashMap<String, String> = HashMap::new();
+
+    if !replace {
+      for snippet in existing.iter().filter(|s| !seen.contains(&s.id)) {
+        names.extend(snippet.names().map(|n| (n.to_string(), snippet.id.clone())));
+      }
+    }
+
+    for snippet in incoming {
+      if snippet.validate().is_err() {
+        plan.invalid.push(snippet.id);
+        continue
+      }
+
+      let collides = snippet.names().any(|n| names.get(n).is_some_and(|id| id != &snippet.id));
+
+      if duplicates.contains(&snippet.id) || collides {
+        if !plan.conflicting.contains(&snippet.id) {
+          plan.conflicting.push(snippet.id);
+        }
+
+        continue
+      }
+
+      names.extend(snippet.names().map(|n| (n.to_string(), snippet.id.clone())));
+
+      match existing.iter().find(|s| s.id == snippet.id) {
+        Some(current) if current.snapshot() == snippet.snapshot() => plan.unchanged += 1,
+        Some(_) => plan.changed.push(snippet),
+        None => plan.added.push(snippet)
+      }
+    }
+
+    if replace {
+      plan.removed = existing.iter()
+        .filter(|s| !seen.contains(&s.id))
+        .map(|s| s.id.clone())
+        .collect();
+    }
+
+    plan
+  }
+
+  /// Whether applying the plan would change anything.
+  pub(super) fn is_empty(&self) -> bool {
+    self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+  }
+
+  /// Ids of the snippets that are never applied.
+  pub(super) fn skipped(&self) -> impl Iterator<Item = &String> {
+    self.conflicting.iter().chain(self.invalid.iter())
+  }
+
+  /// Applies the plan to `snippets`, recording changed titles and contents as
+  /// revisions made by `author`.
+  pub(super) fn apply(&self, snippets: &mut Vec<Snippet>, author: UserId) -> Vec<ChangeRecord> {
+    let mut records = Vec::new();
+
+    for snippet in &self.added {
+      snippets.push(snippet.snapshot());
+      records.push((snippet.id.clone(), None, Some(snippet.snapshot())));
+    }
+
+    for incoming in &self.changed {
+      if let Some(snippet) = snippets.iter_mut().find(|s| s.id == incoming.id) {
+        let before = snippet.snapshot();
+
+        if snippet.title != incoming.title || snippet.content != incoming.content {
+          snippet.revise(author, incoming.title.clone(), incoming.content.clone());
+        }
+
+        *snippet = Snippet { revisions: snippet.revisions.clone(), ..incoming.snapshot() };
+        records.push((snippet.id.clone(), Some(before), Some(snippet.snapshot())));
+      }
+    }
+
+    for snippet in snippets.iter().filter(|s| self.removed.contains(&s.id)) {
+      records.push((snippet.id.clone(), Some(snippet.snapshot()), None));
+    }
+
+    snippets.retain(|s| !self.removed.contains(&s.id));
+
+    records
+  }
+
+  pub(super) fn embed(&self, title: &str, colour: Colour) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title(title)
+      .description(format!("{} snippets are unchanged", self.unchanged))
+      .colour(colour);
+
+    let added: Vec<&str> = self.added.iter().map(|s| s.id.as_str()).collect();
+    let changed: Vec<&str> = self.changed.iter().map(|s| s.id.as_str()).collect();
+    let removed: Vec<&str> = self.removed.iter().map(String::as_str).collect();
+    let conflicting: Vec<&str> = self.conflicting.iter().map(String::as_str).collect();
+    let invalid: Vec<&str> = self.invalid.iter().map(String::as_str).collect();
+
+    let sections = [
+      ("Added", added),
+      ("Changed", changed),
+      ("Removed", removed),
+      ("Conflicting (skipped)", conflicting),
+      ("Invalid (skipped)", invalid)
+    ];
+
+    for (name, ids) in sections {
+      if !ids.is_empty() {
+        embed.field(format!("{name} ({})", ids.len()), list_ids(&ids), false);
+      }
+    }
+
+    embed
+  }
+}
+
+pub(super) async fn export_snippets(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let scope = get_scope(interaction);
+
+  let snippets: Vec<Snippet> = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+
+    state.scope(scope).iter()
+      .map(Snippet::snapshot)
+      .collect()
+  };
+
+  let json = to_vec_pretty(&SnippetFile::Snippets { snippets: snippets.clone() })
+    .expect("Failed to serialize snippets");
+
+  let markdown = snippets.iter()
+    .map(|s| format!("## {}\n\n`{}`\n\n{}\n", s.title, s.id, s.content))
+    .collect::<Vec<String>>()
+    .join("\n---\n\n");
+
+  let name = match scope {
+    Some(guild) => format!("snippets-{guild}"),
+    None => "snippets-global".to_string()
+  };
+
+  let result = interaction.create_followup_message(ctx, |r| r
+    .content(format!("Exported {} snippets", snippets.len()))
+    .add_file(AttachmentType::Bytes { data: Cow::from(json), filename: format!("{name}.json") })
+    .add_file(AttachmentType::Bytes { data: Cow::from(markdown.into_bytes()), filename: format!("{name}.md") })
+  ).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to interaction '{}': {:#?}", interaction.data.name, e)
+  }
+}
+
+pub(super) async fn import_snippets(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let attachment = match arg(interaction, "file") {
+    CommandDataOptionValue::Attachment(attachment) => attachment,
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  let mode = match arg_opt(interaction, "mode") {
+    Some(CommandDataOptionValue::String(mode)) if mode == "merge" => ImportMode::Merge,
+    Some(CommandDataOptionValue::String(mode)) if mode == "replace" => ImportMode::Replace,
+    _ => ImportMode::DryRun
+  };
+
+  let scope = get_scope(interaction);
+
+  if mode != ImportMode::DryRun && scope.is_none() && storage::is_read_only() {
+    let content = "Snippets shared with every server are synced from the snippets directory, change them there instead";
+    return respond_err(ctx, interaction, "Snippets are read-only", content).await
+  }
+
+  if mode != ImportMode::DryRun && scope.is_none() && !is_owner(ctx, interaction).await {
+    let content = "Snippets shared with every server may only be imported by the bot owner";
+    return respond_err(ctx, interaction, "Insufficient permissions", content).await
+  }
+
+  let incoming = match attachment.download().await {
+    Ok(bytes) => match from_slice::<SnippetFile>(&bytes) {
+      Ok(SnippetFile::Snippets { snippets }) | Ok(SnippetFile::List(snippets)) => snippets,
+      Err(e) => {
+        let content = &format!("'{}' is not a valid snippet file: {e}", attachment.filename);
+        return respond_err(ctx, interaction, "Failed to import snippets", content).await
+      }
+    },
+    Err(e) => {
+      let content = &format!("Unable to download '{}': {e}", attachment.filename);
+      return respond_err(ctx, interaction, "Failed to import snippets", content).await
+    }
+  };
+
+  let (plan, records) = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+
+    let plan = ImportPlan::new(state.scope(scope), incoming, mode == ImportMode::Replace);
+    let mut records = Vec::new();
+
+    if mode != ImportMode::DryRun {
+      println!("Importing {} new and {} changed snippets", plan.added.len(), plan.changed.len());
+      records = plan.apply(state.scope_mut(scope), interaction.user.id);
+
+      for (id, _, _) in &records {
+        persistence.mark(Change::Snippet { scope, id: id.clone() });
+      }
+    }
+
+    (plan, records)
+  };
+
+  if mode != ImportMode::DryRun {
+    for (id, before, after) in records {
+      audit(ctx, interaction, Some(&id), before, after).await;
+    }
+  }
+
+  let embed = match mode {
+    ImportMode::DryRun => plan.embed("Snippet import preview", super::ACCENT_COLOUR),
+    _ => plan.embed("Snippets successfully imported", super::OK_COLOUR)
+  };
+
+  respond_embed(ctx, interaction, &embed, false).await
+}
+
+fn get_scope(interaction: &ApplicationCommandInteraction) -> Option<GuildId> {
+  match arg_opt(interaction, "global") {
+    Some(CommandDataOptionValue::Boolean(true)) => None,
+    _ => interaction.guild_id
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::structures::{Platform, SnippetField, SnippetLink, SnippetVariant};
+  use super::*;
+
+  #[test]
+  fn applies_every_changed_field() {
+    let mut snippets = vec![Snippet::new("faq".to_string(), "FAQ".to_string(), "Read the wiki".to_string())];
+
+    let mut incoming = snippets[0].clone();
+    incoming.title = "Frequently asked questions".to_string();
+    incoming.content = "Read the wiki first".to_string();
+    incoming.aliases = vec!["questions".to_string()];
+    incoming.category = Some("General".to_string());
+    incoming.tags = vec!["help".to_string()];
+    incoming.fields = vec![SnippetField { name: "Wiki".to_string(), value: "opentabletdriver.net".to_string(), inline: true }];
+    incoming.image = Some("https://example.com/image.png".to_string());
+    incoming.thumbnail = Some("https://example.com/thumbnail.png".to_string());
+    incoming.colour = Some(0x00ff00);
+    incoming.footer = Some("Still stuck? Ask in #support".to_string());
+    incoming.links = vec![SnippetLink { label: "Wiki".to_string(), url: "https://opentabletdriver.net/Wiki".to_string() }];
+    incoming.variants.insert("de".to_string(), SnippetVariant { title: "FAQ".to_string(), content: "Lies das Wiki".to_string() });
+    incoming.platforms.insert(Platform::Linux, "Run `journalctl`".to_string());
+
+    let plan = ImportPlan::new(&snippets, vec![incoming.clone()], false);
+    assert_eq!(plan.changed.len(), 1);
+
+    plan.apply(&mut snippets, UserId(1));
+    assert!(snippets[0].snapshot() == incoming);
+    assert_eq!(snippets[0].revisions.len(), 1);
+    assert_eq!(snippets[0].revisions[0].content, "Read the wiki");
+  }
+}
@@ -4,37 +4,128 @@ use serenity::builder::CreateEmbed;
 use serenity::http::Http;
 use serenity::model::prelude::command::Command;
 use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::command::CommandType;
+use serenity::model::prelude::{GuildId, UserId};
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::interaction::application_command::CommandDataOption;
 use serenity::model::prelude::interaction::application_command::CommandDataOptionValue;
+use serenity::model::prelude::interaction::autocomplete::AutocompleteInteraction;
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::prelude::interaction::modal::ModalSubmitInteraction;
+use serenity::model::Permissions;
 use serenity::prelude::Context;
 use serenity::prelude::TypeMapKey;
 use serenity::utils::Colour;
 use std::collections::HashMap;
-use crate::structures::State;
-
+use serenity::model::Timestamp;
+use crate::storage::{StorageContainer, UsageLogContainer};
+use crate::structures::{AuditEntry, Owners, Platform, Snippet, SnippetAction, Usage};
+
+mod aliases;
+mod audit;
+mod backups;
+mod browse;
+mod drafts;
+mod import_export;
+mod modals;
+mod permissions;
+mod platforms;
+mod reply;
 mod snippets;
+mod stats;
+mod sync;
 mod utils;
 
-pub async fn register(ctx: &Context) -> ApplicationCommandMap {
-  println!("Registering slash commands...");
+pub use browse::ListFilters;
+pub use modals::PendingModals;
+pub use platforms::{render_for_platform, user_platform, ShownTabs};
+pub use sync::sync_on_start;
+
+/// Clears any global slash commands, as every command is registered per guild.
+pub async fn register(ctx: &Context) {
+  println!("Clearing global slash commands...");
+
+  let result = Command::set_global_application_commands(ctx, |commands| {
+    commands.set_application_commands(Vec::new())
+  }).await;
+
+  if let Err(e) = result {
+    println!("Failed to clear global slash commands: {}", e)
+  }
+}
+
+pub async fn register_guild(ctx: &Context, guild: GuildId) -> ApplicationCommandMap {
+  println!("Registering slash commands for guild {guild}...");
 
-  let mut data = ctx.data.write().await;
-  let state = data.get_mut::<State>()
-    .expect("Failed to get state");
+  let mut commands = ApplicationCommandMap::new();
 
-  let commands = ApplicationCommandMap::new(state);
+  for (action, permissions) in permissions::default_permissions(ctx, guild).await {
+    commands.restrict(action, permissions);
+  }
 
-  match commands.register(ctx).await {
-    Ok(c) => println!("Registered {} slash commands", c.len()),
-    Err(e) => println!("Failed to register slash commands: {}", e)
+  match commands.register(ctx, guild).await {
+    Ok(c) => println!("Registered {} slash commands for guild {guild}", c.len()),
+    Err(e) => println!("Failed to register slash commands for guild {guild}: {}", e)
   }
 
   commands
 }
 
+/// Suggests values for the focused option as the user types.
+pub async fn autocomplete(ctx: &Context, interaction: &AutocompleteInteraction) {
+  let option = match focused(interaction) {
+    Some(option) => option,
+    None => return
+  };
+
+  let query = option.value.as_ref()
+    .and_then(|v| v.as_str())
+    .unwrap_or_default();
+
+  match option.name.as_str() {
+    "id" => snippets::autocomplete_id(ctx, interaction, query).await,
+    "language" => snippets::autocomplete_language(ctx, interaction, query).await,
+    "category" | "tag" => browse::autocomplete_label(ctx, interaction, &option.name, query).await,
+    name => println!("WARNING: Received autocomplete interaction for an invalid option!: {} {}", interaction.data.name, name)
+  }
+}
+
+pub async fn component(ctx: &Context, interaction: &MessageComponentInteraction) {
+  let custom_id = &interaction.data.custom_id;
+
+  match custom_id.split(':').next().unwrap_or_default() {
+    "snippets-search" => browse::change_page(ctx, interaction).await,
+    "snippets-list" => browse::change_list_page(ctx, interaction).await,
+    "snippets-post" => browse::post_snippet(ctx, interaction).await,
+    "snippet-reply" => reply::reply(ctx, interaction).await,
+    "snippet-os" => platforms::change_tab(ctx, interaction).await,
+    "draft-approve" | "draft-reject" => drafts::review_draft(ctx, interaction).await,
+    _ => println!("WARNING: Received invalid message component interaction!: {}", custom_id)
+  }
+}
+
+pub async fn modal(ctx: &Context, interaction: &ModalSubmitInteraction) {
+  match interaction.data.custom_id.split(':').next().unwrap_or_default() {
+    "snippet-modal" => modals::submit_modal(ctx, interaction).await,
+    _ => println!("WARNING: Received invalid modal submit interaction!: {}", interaction.data.custom_id)
+  }
+}
+
 pub async fn interact(ctx: &Context, interaction: &ApplicationCommandInteraction) {
   let name = &interaction.data.name;
 
+  if !permissions::check(ctx, interaction).await {
+    return
+  }
+
+  if modals::opens_modal(interaction) {
+    return modals::open_modal(ctx, interaction).await
+  }
+
+  if name == reply::REPLY_COMMAND {
+    return reply::reply_with_snippet(ctx, interaction).await
+  }
+
   interaction.defer(ctx).await.expect("Failed to defer interaction");
 
   match name.as_str() {
@@ -42,8 +133,22 @@ pub async fn interact(ctx: &Context, interaction: &ApplicationCommandInteraction
     "create-snippet" => snippets::create_snippet(ctx, interaction).await,
     "edit-snippet" => snippets::edit_snippet(ctx, interaction).await,
     "remove-snippet" => snippets::remove_snippet(ctx, interaction).await,
+    "propose-snippet" => drafts::propose_snippet(ctx, interaction).await,
+    "snippet-review" => drafts::snippet_review(ctx, interaction).await,
     "export-snippet" => snippets::export_snippet(ctx, interaction).await,
+    "export-snippets" => import_export::export_snippets(ctx, interaction).await,
+    "import-snippets" => import_export::import_snippets(ctx, interaction).await,
+    "snippet-history" => snippets::snippet_history(ctx, interaction).await,
+    "snippet-rollback" => snippets::snippet_rollback(ctx, interaction).await,
+    "snippet-alias" => aliases::snippet_alias(ctx, interaction).await,
+    "snippet-stats" => stats::snippet_stats(ctx, interaction).await,
+    "snippet-permissions" => permissions::snippet_permissions(ctx, interaction).await,
+    "snippet-platforms" => platforms::snippet_platforms(ctx, interaction).await,
+    "snippets" => browse::snippets(ctx, interaction).await,
     "embed" => utils::embed(ctx, interaction).await,
+    "audit" => audit::audit(ctx, interaction).await,
+    "backups" => backups::backups(ctx, interaction).await,
+    "restore-backup" => backups::restore_backup(ctx, interaction).await,
     _ => {
       println!("WARNING: Received invalid application command interaction!: {}", name);
 
@@ -68,13 +173,17 @@ impl TypeMapKey for ApplicationCommandMap {
 }
 
 impl ApplicationCommandMap {
-  pub fn new(state: &State) -> ApplicationCommandMap {
+  pub fn new() -> ApplicationCommandMap {
     let mut id_opt = CreateApplicationCommandOption::default();
     id_opt.name("id")
       .description("The snippet's id")
       .kind(CommandOptionType::String)
       .required(true);
 
+    let snippet_id_opt = id_opt.clone()
+      .set_autocomplete(true)
+      .clone();
+
     let mut title_opt = CreateApplicationCommandOption::default();
     title_opt.name("title")
       .description("The snippet's title")
@@ -85,29 +194,267 @@ impl ApplicationCommandMap {
       .description("The snippet's content")
       .kind(CommandOptionType::String);
 
+    let mut category_opt = CreateApplicationCommandOption::default();
+    category_opt.name("category")
+      .description("The topic the snippet is listed under, such as Linux or Plugins")
+      .kind(CommandOptionType::String)
+      .set_autocomplete(true);
+
+    let mut tags_opt = CreateApplicationCommandOption::default();
+    tags_opt.name("tags")
+      .description("Comma separated tags for finding the snippet")
+      .kind(CommandOptionType::String);
+
+    let appearance = [
+      ("colour", "The embed's colour, such as #ff00ff"),
+      ("image", "The URL of an image shown below the content"),
+      ("thumbnail", "The URL of an image shown beside the content"),
+      ("footer", "The embed's footer"),
+      ("fields", "Embed fields separated by \\n, each as 'name | value' or 'name | value | inline'"),
+      ("links", "Link buttons separated by \\n, each as 'label | url'")
+    ];
+
+    let mut os_opt = CreateApplicationCommandOption::default();
+    os_opt.name("os")
+      .description("The operating system")
+      .kind(CommandOptionType::String);
+
+    for platform in Platform::ALL {
+      os_opt.add_string_choice(platform.label(), platform.name());
+    }
+
     let snippet = CreateApplicationCommand::default()
       .description("Shows a snippet")
+      .add_option(snippet_id_opt.clone())
+      .create_option(|o| o
+        .name("args")
+        .description("Values for the snippet's {1}, {2}... placeholders, quote values containing spaces")
+        .kind(CommandOptionType::String)
+      )
+      .create_option(|o| o
+        .name("language")
+        .description("The translation to show, your Discord language if left out")
+        .kind(CommandOptionType::String)
+        .set_autocomplete(true)
+      )
+      .add_option(os_opt.clone()
+        .description("The OS section to show, chosen by the user's roles if left out")
+        .clone()
+      )
+      .create_option(|o| o
+        .name("user")
+        .description("The user the snippet is for, whose roles choose the OS section")
+        .kind(CommandOptionType::User)
+      )
       .clone();
 
-    let create_snippet = CreateApplicationCommand::default()
+    let mut create_snippet = CreateApplicationCommand::default()
       .description("Creates a snippet")
       .add_option(id_opt)
       .add_option(title_opt.required(true).clone())
-      .add_option(content_opt.required(true).clone())
+      .add_option(content_opt.clone()
+        .description("The snippet's content, leave out to write it in a multi-line editor")
+        .clone()
+      )
+      .add_option(category_opt.clone())
+      .add_option(tags_opt.clone())
+      .create_option(|o| o
+        .name("global")
+        .description("Whether the snippet is shared with every server, only the bot owner may do this")
+        .kind(CommandOptionType::Boolean)
+      )
       .clone();
 
-    let edit_snippet = CreateApplicationCommand::default()
-      .description("Edits a snippet")
+    let mut edit_snippet = CreateApplicationCommand::default()
+      .description("Edits a snippet, opening a multi-line editor if only the id is given")
+      .add_option(snippet_id_opt.clone())
       .add_option(title_opt.required(false).clone())
       .add_option(content_opt.required(false).clone())
+      .add_option(category_opt
+        .description("The topic the snippet is listed under, '-' removes it")
+        .clone()
+      )
+      .add_option(tags_opt
+        .description("Comma separated tags for finding the snippet, '-' removes them")
+        .clone()
+      )
+      .create_option(|o| o
+        .name("language")
+        .description("The translation to edit instead, such as de or pt-BR, where content '-' removes it")
+        .kind(CommandOptionType::String)
+        .set_autocomplete(true)
+      )
       .clone();
 
+    for (name, description) in appearance {
+      create_snippet.create_option(|o| o
+        .name(name)
+        .description(description)
+        .kind(CommandOptionType::String)
+      );
+
+      edit_snippet.create_option(|o| o
+        .name(name)
+        .description(format!("{description}, '-' removes it"))
+        .kind(CommandOptionType::String)
+      );
+    }
+
+    for platform in Platform::ALL {
+      let description = format!("A section shown after the content to {} users", platform.label());
+
+      create_snippet.create_option(|o| o
+        .name(platform.name())
+        .description(&description)
+        .kind(CommandOptionType::String)
+      );
+
+      edit_snippet.create_option(|o| o
+        .name(platform.name())
+        .description(format!("{description}, '-' removes it"))
+        .kind(CommandOptionType::String)
+      );
+    }
+
     let remove_snippet = CreateApplicationCommand::default()
       .description("Removes a snippet")
+      .add_option(snippet_id_opt.clone())
       .clone();
 
     let export_snippet = CreateApplicationCommand::default()
       .description("Exports a snippet for user editing")
+      .add_option(snippet_id_opt.clone())
+      .clone();
+
+    let export_snippets = CreateApplicationCommand::default()
+      .description("Exports every snippet as JSON and Markdown files")
+      .create_option(|o| o
+        .name("global")
+        .description("Export the snippets shared with every server instead")
+        .kind(CommandOptionType::Boolean)
+      )
+      .clone();
+
+    let import_snippets = CreateApplicationCommand::default()
+      .description("Imports snippets from a JSON file created by export-snippets")
+      .create_option(|o| o
+        .name("file")
+        .description("The JSON file to import")
+        .kind(CommandOptionType::Attachment)
+        .required(true)
+      )
+      .create_option(|o| o
+        .name("mode")
+        .description("How to apply the file, only previews the changes by default")
+        .kind(CommandOptionType::String)
+        .add_string_choice("Merge: add new and update changed snippets", "merge")
+        .add_string_choice("Replace: also remove snippets missing from the file", "replace")
+        .add_string_choice("Dry run: only preview the changes", "dry-run")
+      )
+      .create_option(|o| o
+        .name("global")
+        .description("Import into the snippets shared with every server, only the bot owner may do this")
+        .kind(CommandOptionType::Boolean)
+      )
+      .clone();
+
+    let snippet_history = CreateApplicationCommand::default()
+      .description("Lists the revisions of a snippet")
+      .add_option(snippet_id_opt.clone())
+      .clone();
+
+    let snippet_rollback = CreateApplicationCommand::default()
+      .description("Restores a snippet to the title and content replaced by a revision")
+      .add_option(snippet_id_opt.clone())
+      .create_option(|o| o
+        .name("revision")
+        .description("The revision number from snippet-history")
+        .kind(CommandOptionType::Integer)
+        .min_int_value(1)
+        .required(true)
+      )
+      .clone();
+
+    let snippet_alias = CreateApplicationCommand::default()
+      .description("Manages the other names a snippet can be found by")
+      .create_option(|o| o
+        .name("add")
+        .description("Adds an alias to a snippet")
+        .kind(CommandOptionType::SubCommand)
+        .add_sub_option(snippet_id_opt.clone())
+        .create_sub_option(|o| o
+          .name("alias")
+          .description("The other name for the snippet")
+          .kind(CommandOptionType::String)
+          .required(true)
+        )
+      )
+      .create_option(|o| o
+        .name("remove")
+        .description("Removes an alias from a snippet")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|o| o
+          .name("alias")
+          .description("The alias to remove")
+          .kind(CommandOptionType::String)
+          .required(true)
+        )
+      )
+      .clone();
+
+    let snippet_stats = CreateApplicationCommand::default()
+      .description("Shows the most used and never used snippets")
+      .create_option(|o| o
+        .name("days")
+        .description("How many days back to count, 30 by default")
+        .kind(CommandOptionType::Integer)
+        .min_int_value(1)
+        .max_int_value(365)
+      )
+      .create_option(|o| o
+        .name("limit")
+        .description("The number of most used snippets to show, 10 by default")
+        .kind(CommandOptionType::Integer)
+        .min_int_value(1)
+        .max_int_value(25)
+      )
+      .clone();
+
+    let snippets = CreateApplicationCommand::default()
+      .description("Finds snippets")
+      .create_option(|o| o
+        .name("search")
+        .description("Searches snippets by id, title and content, tolerating typos")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|o| o
+          .name("query")
+          .description("The words to search for")
+          .kind(CommandOptionType::String)
+          .required(true)
+        )
+      )
+      .create_option(|o| o
+        .name("list")
+        .description("Lists snippets, optionally only those in a category or with a tag")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|o| o
+          .name("category")
+          .description("Only list snippets in this category")
+          .kind(CommandOptionType::String)
+          .set_autocomplete(true)
+        )
+        .create_sub_option(|o| o
+          .name("tag")
+          .description("Only list snippets with this tag")
+          .kind(CommandOptionType::String)
+          .set_autocomplete(true)
+        )
+      )
+      .create_option(|o| o
+        .name("sync")
+        .description("Replaces the shared snippets with the snippets directory, only the bot owner may do this")
+        .kind(CommandOptionType::SubCommand)
+      )
       .clone();
 
     let embed = CreateApplicationCommand::default()
@@ -144,26 +491,192 @@ impl ApplicationCommandMap {
       )
       .clone();
 
+    let audit = CreateApplicationCommand::default()
+      .description("Lists recent changes to snippets and other state")
+      .default_member_permissions(Permissions::ADMINISTRATOR)
+      .create_option(|o| o
+        .name("user")
+        .description("Only show changes made by this user")
+        .kind(CommandOptionType::User)
+      )
+      .create_option(|o| o
+        .name("id")
+        .description("Only show changes to this snippet id")
+        .kind(CommandOptionType::String)
+      )
+      .create_option(|o| o
+        .name("since")
+        .description("Only show changes made on or after this date (YYYY-MM-DD)")
+        .kind(CommandOptionType::String)
+      )
+      .create_option(|o| o
+        .name("until")
+        .description("Only show changes made on or before this date (YYYY-MM-DD)")
+        .kind(CommandOptionType::String)
+      )
+      .create_option(|o| o
+        .name("limit")
+        .description("The number of changes to show, 10 by default")
+        .kind(CommandOptionType::Integer)
+        .min_int_value(1)
+        .max_int_value(25)
+      )
+      .clone();
+
+    let backups = CreateApplicationCommand::default()
+      .description("Lists the available state backups")
+      .default_member_permissions(Permissions::ADMINISTRATOR)
+      .clone();
+
+    let restore_backup = CreateApplicationCommand::default()
+      .description("Restores the state from a backup")
+      .default_member_permissions(Permissions::ADMINISTRATOR)
+      .create_option(|o| o
+        .name("name")
+        .description("The backup's file name")
+        .kind(CommandOptionType::String)
+        .required(true)
+      )
+      .clone();
+
+    let mut action_opt = CreateApplicationCommandOption::default();
+    action_opt.name("action")
+      .description("The kind of change to snippets")
+      .kind(CommandOptionType::String)
+      .required(true);
+
+    for action in SnippetAction::ALL {
+      action_opt.add_string_choice(action.name(), action.name());
+    }
+
+    let mut role_opt = CreateApplicationCommandOption::default();
+    role_opt.name("role")
+      .description("The role that may take the action")
+      .kind(CommandOptionType::Role)
+      .required(true);
+
+    let snippet_permissions = CreateApplicationCommand::default()
+      .description("Manages which roles may create, edit and remove snippets in this server")
+      .default_member_permissions(Permissions::MANAGE_GUILD)
+      .create_option(|o| o
+        .name("show")
+        .description("Shows which roles may take each action")
+        .kind(CommandOptionType::SubCommand)
+      )
+      .create_option(|o| o
+        .name("grant")
+        .description("Allows a role to take an action, which then only allowed roles may take")
+        .kind(CommandOptionType::SubCommand)
+        .add_sub_option(action_opt.clone())
+        .add_sub_option(role_opt.clone())
+      )
+      .create_option(|o| o
+        .name("revoke")
+        .description("Stops a role from taking an action, which everyone may take once no roles are left")
+        .kind(CommandOptionType::SubCommand)
+        .add_sub_option(action_opt)
+        .add_sub_option(role_opt)
+      )
+      .clone();
+
+    let propose_snippet = CreateApplicationCommand::default()
+      .description("Proposes a new snippet or a change to one, which is applied once approved")
+      .add_option(snippet_id_opt.clone())
+      .add_option(title_opt.clone())
+      .add_option(content_opt
+        .description("The snippet's content, leave out to write it in a multi-line editor")
+        .clone()
+      )
+      .clone();
+
+    let snippet_review = CreateApplicationCommand::default()
+      .description("Sets the channel proposed snippets are posted in for review")
+      .default_member_permissions(Permissions::MANAGE_GUILD)
+      .create_option(|o| o
+        .name("channel")
+        .description("The review channel")
+        .kind(CommandOptionType::Channel)
+        .required(true)
+      )
+      .clone();
+
+    let snippet_platforms = CreateApplicationCommand::default()
+      .description("Manages which roles tell the OS a member uses, choosing the snippet sections they are shown")
+      .default_member_permissions(Permissions::MANAGE_GUILD)
+      .create_option(|o| o
+        .name("show")
+        .description("Shows which roles mark each OS")
+        .kind(CommandOptionType::SubCommand)
+      )
+      .create_option(|o| o
+        .name("add")
+        .description("Marks members with a role as users of an OS")
+        .kind(CommandOptionType::SubCommand)
+        .add_sub_option(os_opt.clone().required(true).clone())
+        .create_sub_option(|o| o
+          .name("role")
+          .description("The role that marks users of the OS")
+          .kind(CommandOptionType::Role)
+          .required(true)
+        )
+      )
+      .create_option(|o| o
+        .name("remove")
+        .description("Stops a role from marking users of an OS")
+        .kind(CommandOptionType::SubCommand)
+        .add_sub_option(os_opt.required(true).clone())
+        .create_sub_option(|o| o
+          .name("role")
+          .description("The role that marks users of the OS")
+          .kind(CommandOptionType::Role)
+          .required(true)
+        )
+      )
+      .clone();
+
+    let reply_with_snippet = CreateApplicationCommand::default()
+      .kind(CommandType::Message)
+      .clone();
+
     let mut commands = ApplicationCommandMap(CommandHashMap::new());
 
     commands.insert("snippet", snippet);
     commands.insert("create-snippet", create_snippet);
     commands.insert("edit-snippet", edit_snippet);
     commands.insert("remove-snippet", remove_snippet);
+    commands.insert("propose-snippet", propose_snippet);
+    commands.insert("snippet-review", snippet_review);
     commands.insert("export-snippet", export_snippet);
+    commands.insert("export-snippets", export_snippets);
+    commands.insert("import-snippets", import_snippets);
+    commands.insert("snippet-history", snippet_history);
+    commands.insert("snippet-rollback", snippet_rollback);
+    commands.insert("snippet-alias", snippet_alias);
+    commands.insert("snippet-stats", snippet_stats);
+    commands.insert("snippet-permissions", snippet_permissions);
+    commands.insert("snippet-platforms", snippet_platforms);
+    commands.insert("snippets", snippets);
     commands.insert("embed", embed);
+    commands.insert("audit", audit);
+    commands.insert("backups", backups);
+    commands.insert("restore-backup", restore_backup);
+    commands.insert(reply::REPLY_COMMAND, reply_with_snippet);
+
+    commands
+  }
+
+  /// Hides the commands that take `action` from members without
+  /// `permissions` by default.
+  fn restrict(&mut self, action: SnippetAction, permissions: Permissions) {
+    let commands = permissions::ACTION_COMMANDS.iter()
+      .filter(|(a, _)| *a == action)
+      .flat_map(|(_, commands)| commands.iter());
 
-    for (name, command) in commands.0.iter_mut() {
-      match *name {
-        "snippet" => snippets::sync_snippets(state, command),
-        "remove-snippet" => snippets::sync_snippets(state, command),
-        "export-snippet" => snippets::sync_snippets(state, command),
-        "edit-snippet" => snippets::sync_snippets(state, command),
-        _ => ()
+    for name in commands {
+      if let Some(command) = self.0.get_mut(name) {
+        command.default_member_permissions(permissions);
       }
     }
-
-    commands
   }
 
   fn insert(&mut self, k: &'static str, v: CreateApplicationCommand) -> Option<CreateApplicationCommand> {
@@ -180,19 +693,25 @@ impl ApplicationCommandMap {
       .collect::<Vec<CreateApplicationCommand>>()
   }
 
-  pub async fn register(&self, http: impl AsRef<Http>) -> Result<Vec<Command>, serenity::Error> {
-    Command::set_global_application_commands(http, |commands| {
+  pub async fn register(&self, http: impl AsRef<Http>, guild: GuildId) -> Result<Vec<Command>, serenity::Error> {
+    guild.set_application_commands(http, |commands| {
       commands.set_application_commands(self.builders())
     }).await
   }
 }
 
 pub fn arg(interaction: &ApplicationCommandInteraction, name: &'static str) -> CommandDataOptionValue {
-  arg_opt(interaction, name).expect(&format!("No '{name}' argument provided")).clone()
+  arg_opt(interaction, name).unwrap_or_else(|| panic!("No '{name}' argument provided"))
 }
 
+/// Gets the argument `name`, looking inside the subcommand if one was used.
 pub fn arg_opt(interaction: &ApplicationCommandInteraction, name: &'static str) -> Option<CommandDataOptionValue> {
-  let opt = interaction.data.options.iter()
+  let options = match interaction.data.options.first() {
+    Some(o) if o.kind == CommandOptionType::SubCommand => &o.options,
+    _ => &interaction.data.options
+  };
+
+  let opt = options.iter()
     .find(|o| o.name == name);
 
   if let Some(opt) = opt {
@@ -202,6 +721,41 @@ pub fn arg_opt(interaction: &ApplicationCommandInteraction, name: &'static str)
   }
 }
 
+/// The option being autocompleted, looking inside the subcommand if one is
+/// being used.
+fn focused(interaction: &AutocompleteInteraction) -> Option<&CommandDataOption> {
+  let options = match interaction.data.options.first() {
+    Some(o) if o.kind == CommandOptionType::SubCommand => &o.options,
+    _ => &interaction.data.options
+  };
+
+  options.iter().find(|o| o.focused)
+}
+
+pub fn subcommand(interaction: &ApplicationCommandInteraction) -> Option<&str> {
+  interaction.data.options.first()
+    .filter(|o| o.kind == CommandOptionType::SubCommand)
+    .map(|o| o.name.as_str())
+}
+
+/// Formats `ids` as an inline list that fits in an embed field.
+pub fn list_ids(ids: &[&str]) -> String {
+  let mut list = String::new();
+
+  for (i, id) in ids.iter().enumerate() {
+    let item = format!("`{id}` ");
+
+    if list.len() + item.len() > 1000 {
+      list.push_str(&format!("and {} more", ids.len() - i));
+      break
+    }
+
+    list.push_str(&item);
+  }
+
+  list
+}
+
 pub async fn respond_embed(ctx: &Context, interaction: &ApplicationCommandInteraction, embed: &CreateEmbed, ephemeral: bool) {
   let result = interaction.create_followup_message(ctx, |r| r
     .add_embed(embed.clone())
@@ -233,17 +787,67 @@ pub async fn respond_err(ctx: &Context, interaction: &ApplicationCommandInteract
   respond_embed(ctx, interaction, embed, false).await;
 }
 
-pub async fn update_commands(ctx: &Context) {
-  let mut data = ctx.data.write().await;
-  let state = data.get_mut::<State>()
-    .expect("Failed to get state");
+/// Records a change made by `interaction` in the audit log. `before` and
+/// `after` are the changed snippet, if any, on either side of the change.
+pub async fn audit(ctx: &Context, interaction: &ApplicationCommandInteraction, snippet: Option<&str>, before: Option<Snippet>, after: Option<Snippet>) {
+  let entry = AuditEntry {
+    timestamp: Timestamp::now(),
+    actor: interaction.user.id,
+    guild: interaction.guild_id,
+    command: interaction.data.name.clone(),
+    snippet: snippet.map(str::to_string),
+    before,
+    after,
+    details: None
+  };
+
+  record_audit(ctx, entry).await
+}
 
-  println!("Updating commands...");
-  let command_map = ApplicationCommandMap::new(state);
+/// Records a change to the guild's settings made by `interaction`, described
+/// by `details`, in the audit log.
+pub async fn audit_settings(ctx: &Context, interaction: &ApplicationCommandInteraction, details: String) {
+  let entry = AuditEntry {
+    timestamp: Timestamp::now(),
+    actor: interaction.user.id,
+    guild: interaction.guild_id,
+    command: interaction.data.name.clone(),
+    snippet: None,
+    before: None,
+    after: None,
+    details: Some(details)
+  };
+
+  record_audit(ctx, entry).await
+}
 
-  println!("Registering newly updated commands");
-  match command_map.register(ctx).await {
-    Ok(commands) => println!("Successfully updated {} commands", commands.len()),
-    Err(e) => println!("Failed to update commands: {e}")
-  }
+/// Records `entry` in the audit log, writing it on a blocking thread.
+pub async fn record_audit(ctx: &Context, entry: AuditEntry) {
+  let storage = {
+    let data = ctx.data.read().await;
+    data.get::<StorageContainer>().expect("Failed to get storage").clone()
+  };
+
+  tokio::task::spawn_blocking(move || {
+    if let Err(e) = storage.append_audit(&entry) {
+      println!("Failed to record audit entry for '{}': {e}", entry.command);
+    }
+  }).await.expect("Failed to join storage task")
+}
+
+/// Records that a snippet was shown, for usage statistics and ranking.
+pub async fn record_usage(ctx: &Context, usage: Usage) {
+  let data = ctx.data.read().await;
+  data.get::<UsageLogContainer>().expect("Failed to get usage log").record(usage);
+}
+
+pub async fn is_owner(ctx: &Context, interaction: &ApplicationCommandInteraction) -> bool {
+  is_owner_user(ctx, interaction.user.id).await
+}
+
+pub async fn is_owner_user(ctx: &Context, user: UserId) -> bool {
+  let data = ctx.data.read().await;
+  let owners = data.get::<Owners>().expect("Failed to get owners");
+
+  owners.contains(&user)
 }
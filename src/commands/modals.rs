@@ -0,0 +1,215 @@
+use serenity::builder::CreateEmbed;
+use serenity::model::application::component::{ActionRowComponent, InputTextStyle};
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::prelude::interaction::modal::ModalSubmitInteraction;
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::Timestamp;
+use serenity::prelude::{Context, TypeMapKey};
+use std::collections::HashMap;
+use crate::structures::State;
+
+use super::{arg, arg_opt, respond_err, ERROR_COLOUR};
+use super::drafts::save_proposal;
+use super::snippets::{get_snippet, save_create, save_edit};
+
+/// How long an unsubmitted modal is remembered for, in seconds.
+const MODAL_LIFETIME: i64 = 60 * 60;
+
+/// The longest text a modal's text input can hold.
+const MAX_INPUT_LENGTH: usize = 4000;
+
+/// The commands waiting for the modal they opened to be submitted, by the
+/// modal's custom id.
+pub struct PendingModals;
+
+impl TypeMapKey for PendingModals {
+  type Value = HashMap<String, ApplicationCommandInteraction>;
+}
+
+/// Whether `interaction` is answered with a modal to write the snippet in,
+/// which is when `create-snippet` or `propose-snippet` is used without
+/// content or `edit-snippet` with nothing but an id and a language.
+pub fn opens_modal(interaction: &ApplicationCommandInteraction) -> bool {
+  match interaction.data.name.as_str() {
+    "create-snippet" | "propose-snippet" => arg_opt(interaction, "content").is_none(),
+    "edit-snippet" => interaction.data.options.iter().all(|o| o.name == "id" || o.name == "language"),
+    _ => false
+  }
+}
+
+/// Opens a modal with the title and content of the snippet `interaction`
+/// creates, edits or proposes, prefilled with the current ones when editing
+/// or proposing a change to the server's snippet.
+pub async fn open_modal(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let id = match arg(interaction, "id") {
+    CommandDataOptionValue::String(id) => id,
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  let (heading, title, content) = match interaction.data.name.as_str() {
+    "create-snippet" => match arg_opt(interaction, "title") {
+      Some(CommandDataOptionValue::String(title)) => ("Create snippet", title, String::new()),
+      _ => ("Create snippet", String::new(), String::new())
+    },
+    "propose-snippet" => {
+      let current = {
+        let data = ctx.data.read().await;
+        let state = data.get::<State>().expect("Failed to get state");
+
+        interaction.guild_id.and_then(|guild| state.scope(Some(guild)).iter().find(|s| s.matches(&id)).cloned())
+      };
+
+      match (current, arg_opt(interaction, "title")) {
+        (Some(snippet), Some(CommandDataOptionValue::String(title))) => ("Propose snippet", title, snippet.content),
+        (Some(snippet), _) => ("Propose snippet", snippet.title, snippet.content),
+        (None, Some(CommandDataOptionValue::String(title))) => ("Propose snippet", title, String::new()),
+        (None, _) => ("Propose snippet", String::new(), String::new())
+      }
+    },
+    _ => {
+      let language = match arg_opt(interaction, "language") {
+        Some(CommandDataOptionValue::String(language)) => Some(language),
+        _ => None
+      };
+
+      // A new translation starts from the default title and content
+      let snippet = get_snippet(ctx, interaction.guild_id, &id).await
+        .map(|s| match &language {
+          Some(language) => s.localized(language),
+          None => s
+        });
+
+      match snippet {
+        Some(snippet) if snippet.content.chars().count() <= MAX_INPUT_LENGTH => ("Edit snippet", snippet.title, snippet.content),
+        Some(_) => {
+          let content = &format!("The content of '{id}' is too long to edit here, use the content option instead");
+          return defer_and_respond_err(ctx, interaction, "Failed to edit snippet", content).await
+        },
+        None => {
+          let content = &format!("The snippet '{id}' does not exist");
+          return defer_and_respond_err(ctx, interaction, "Failed to edit snippet", content).await
+        }
+      }
+    }
+  };
+
+  let custom_id = format!("snippet-modal:{}", interaction.id);
+
+  {
+    let mut data = ctx.data.write().await;
+    let pending = data.get_mut::<PendingModals>().expect("Failed to get pending modals");
+    let now = Timestamp::now().unix_timestamp();
+
+    pending.retain(|_, i| now - i.id.created_at().unix_timestamp() < MODAL_LIFETIME);
+    pending.insert(custom_id.clone(), interaction.clone());
+  }
+
+  let result = interaction.create_interaction_response(ctx, |r| r
+    .kind(InteractionResponseType::Modal)
+    .interaction_response_data(|d| d
+      .custom_id(&custom_id)
+      .title(format!("{heading} '{id}'").chars().take(45).collect::<String>())
+      .components(|c| c
+        .create_action_row(|row| row.create_input_text(|i| {
+          i.custom_id("title")
+            .label("Title")
+            .style(InputTextStyle::Short)
+            .max_length(256)
+            .required(true);
+
+          if !title.is_empty() {
+            i.value(&title);
+          }
+
+          i
+        }))
+        .create_action_row(|row| row.create_input_text(|i| {
+          i.custom_id("content")
+            .label("Content")
+            .style(InputTextStyle::Paragraph)
+            .max_length(MAX_INPUT_LENGTH as u64)
+            .required(true);
+
+          if !content.is_empty() {
+            i.value(&content);
+          }
+
+          i
+        }))
+      )
+    )
+  ).await;
+
+  if let Err(e) = result {
+    println!("Failed to open modal for interaction '{}': {:#?}", interaction.data.name, e)
+  }
+}
+
+/// Saves the snippet written in a modal opened by `open_modal`, applying the
+/// other options of the command that opened it.
+pub async fn submit_modal(ctx: &Context, interaction: &ModalSubmitInteraction) {
+  interaction.defer(ctx).await.expect("Failed to defer interaction");
+
+  let command = {
+    let mut data = ctx.data.write().await;
+    let pending = data.get_mut::<PendingModals>().expect("Failed to get pending modals");
+    pending.remove(&interaction.data.custom_id)
+  };
+
+  let command = match command {
+    Some(command) => command,
+    None => {
+      let content = "This snippet editor has expired, use the command again";
+      return respond_modal(ctx, interaction, error_embed("Failed to save snippet", content)).await
+    }
+  };
+
+  let value = |name: &str| interaction.data.components.iter()
+    .flat_map(|row| row.components.iter())
+    .find_map(|c| match c {
+      ActionRowComponent::InputText(input) if input.custom_id == name => Some(input.value.clone()),
+      _ => None
+    })
+    .unwrap_or_default();
+
+  let id = match arg(&command, "id") {
+    CommandDataOptionValue::String(id) => id,
+    _ => panic!("Invalid arguments provided to command: {}", command.data.name)
+  };
+
+  let result = match command.data.name.as_str() {
+    "create-snippet" => save_create(ctx, &command, &id, value("title"), value("content")).await,
+    "propose-snippet" => save_proposal(ctx, &command, &id, Some(value("title")), Some(value("content"))).await,
+    _ => save_edit(ctx, &command, &id, Some(value("title")), Some(value("content"))).await
+  };
+
+  let embed = match result {
+    Ok(embed) => embed,
+    Err((title, content)) => error_embed(title, &content)
+  };
+
+  respond_modal(ctx, interaction, embed).await
+}
+
+async fn respond_modal(ctx: &Context, interaction: &ModalSubmitInteraction, embed: CreateEmbed) {
+  let result = interaction.create_followup_message(ctx, |r| r.add_embed(embed)).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to modal '{}': {:#?}", interaction.data.custom_id, e)
+  }
+}
+
+/// Responds to `interaction` with an error when it was going to be answered
+/// with a modal, and so has not been deferred yet.
+async fn defer_and_respond_err(ctx: &Context, interaction: &ApplicationCommandInteraction, title: &str, content: &str) {
+  interaction.defer(ctx).await.expect("Failed to defer interaction");
+  respond_err(ctx, interaction, title, content).await
+}
+
+fn error_embed(title: &str, content: &str) -> CreateEmbed {
+  CreateEmbed::default()
+    .title(title)
+    .description(content)
+    .colour(ERROR_COLOUR)
+    .clone()
+}
@@ -0,0 +1,264 @@
+use serenity::builder::CreateEmbed;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::prelude::{GuildId, Member, RoleId, UserId};
+use serenity::model::Permissions;
+use serenity::prelude::Context;
+use crate::storage::{Change, PersistenceContainer};
+use crate::structures::{SnippetAction, State};
+
+use super::{arg, arg_opt, audit_settings, is_owner, is_owner_user, register_guild, respond_embed, respond_err, respond_ok, subcommand, ERROR_COLOUR};
+
+/// The commands that take each action, which are hidden from members without
+/// the permissions shared by the roles allowed to take it.
+pub(super) const ACTION_COMMANDS: [(SnippetAction, &[&str]); 4] = [
+  (SnippetAction::Create, &["create-snippet"]),
+  (SnippetAction::Edit, &["edit-snippet", "snippet-alias", "snippet-rollback"]),
+  (SnippetAction::Remove, &["remove-snippet"]),
+  (SnippetAction::Propose, &["propose-snippet"])
+];
+
+/// Whether the user of `interaction` may take the actions it takes in its
+/// guild, responding with an ephemeral denial if not.
+pub(super) async fn check(ctx: &Context, interaction: &ApplicationCommandInteraction) -> bool {
+  let guild = match interaction.guild_id {
+    Some(guild) => guild,
+    None => return true
+  };
+
+  for action in actions(interaction) {
+    let roles = match denied(ctx, guild, interaction.user.id, interaction.member.as_ref(), action).await {
+      Some(roles) => roles,
+      None => continue
+    };
+
+    let result = interaction.create_interaction_response(ctx, |r| r
+      .kind(InteractionResponseType::ChannelMessageWithSource)
+      .interaction_response_data(|d| d
+        .ephemeral(true)
+        .embed(|e| e
+          .title("Insufficient permissions")
+          .description(denial(action, &roles))
+          .colour(ERROR_COLOUR)
+        )
+      )
+    ).await;
+
+    if let Err(e) = result {
+      println!("Failed to respond to interaction '{}': {:#?}", interaction.data.name, e)
+    }
+
+    return false
+  }
+
+  true
+}
+
+/// The roles allowed to take `action` in `guild` if `user` may not take it,
+/// or `None` if they may. Owners and administrators may take any action.
+pub(super) async fn denied(ctx: &Context, guild: GuildId, user: UserId, member: Option<&Member>, action: SnippetAction) -> Option<Vec<RoleId>> {
+  let admin = member.and_then(|m| m.permissions).is_some_and(|p| p.administrator());
+
+  if admin || is_owner_user(ctx, user).await {
+    return None
+  }
+
+  let roles = {
+    let data = ctx.data.read().await;
+    data.get::<State>().expect("Failed to get state").settings(guild).roles.remove(&action)?
+  };
+
+  let member_roles = member.map(|m| m.roles.as_slice()).unwrap_or_default();
+  (!roles.is_empty() && !roles.iter().any(|r| member_roles.contains(r))).then_some(roles)
+}
+
+/// Why `user` may not review drafts that take `action` in `guild`, or `None`
+/// if they may. Reviewing needs Manage Server or one of the roles allowed to
+/// take the action, as anyone may take actions that are not restricted.
+pub(super) async fn review_denied(ctx: &Context, guild: GuildId, user: UserId, member: Option<&Member>, action: SnippetAction) -> Option<String> {
+  let manager = member.and_then(|m| m.permissions).is_some_and(|p| p.administrator() || p.manage_guild());
+
+  if manager || is_owner_user(ctx, user).await {
+    return None
+  }
+
+  let roles = {
+    let data = ctx.data.read().await;
+    data.get::<State>().expect("Failed to get state").settings(guild).roles.remove(&action).unwrap_or_default()
+  };
+
+  let member_roles = member.map(|m| m.roles.as_slice()).unwrap_or_default();
+
+  match roles.iter().any(|r| member_roles.contains(r)) {
+    true => None,
+    false if roles.is_empty() => Some("Only members who can manage the server may review this draft".to_string()),
+    false => Some(format!("Only members who can manage the server or with {} may review this draft", mention_roles(&roles)))
+  }
+}
+
+pub(super) fn denial(action: SnippetAction, roles: &[RoleId]) -> String {
+  format!("Only members with {} may {} snippets in this server", mention_roles(roles), action.name())
+}
+
+/// The actions `interaction` would take.
+fn actions(interaction: &ApplicationCommandInteraction) -> Vec<SnippetAction> {
+  let name = interaction.data.name.as_str();
+
+  if name == "import-snippets" {
+    return match arg_opt(interaction, "mode") {
+      Some(CommandDataOptionValue::String(mode)) if mode == "merge" => vec![SnippetAction::Create, SnippetAction::Edit],
+      Some(CommandDataOptionValue::String(mode)) if mode == "replace" => SnippetAction::ALL.to_vec(),
+      _ => Vec::new()
+    }
+  }
+
+  ACTION_COMMANDS.iter()
+    .filter(|(_, commands)| commands.contains(&name))
+    .map(|(action, _)| *action)
+    .collect()
+}
+
+/// The permissions every role allowed to take each restricted action has
+/// beyond those of `@everyone`, which become the default member permissions
+/// of the action's commands in `guild`.
+pub(super) async fn default_permissions(ctx: &Context, guild: GuildId) -> Vec<(SnippetAction, Permissions)> {
+  let settings = {
+    let data = ctx.data.read().await;
+    data.get::<State>().expect("Failed to get state").settings(guild)
+  };
+
+  let roles = match guild.to_guild_cached(ctx) {
+    Some(guild) => guild.roles,
+    None => return Vec::new()
+  };
+
+  let everyone = roles.get(&RoleId(guild.0))
+    .map(|r| r.permissions)
+    .unwrap_or_else(Permissions::empty);
+
+  settings.roles.iter()
+    .filter_map(|(action, allowed)| {
+      let shared = allowed.iter()
+        .filter_map(|r| roles.get(r))
+        .map(|r| r.permissions)
+        .reduce(|a, b| a & b)?;
+
+      let shared = shared - everyone;
+      (!shared.is_empty()).then_some((*action, shared))
+    })
+    .collect()
+}
+
+pub(super) async fn snippet_permissions(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let guild = match interaction.guild_id {
+    Some(guild) => guild,
+    None => return respond_err(ctx, interaction, "Failed to change permissions", "Permissions can only be changed in a server").await
+  };
+
+  let manager = interaction.member.as_ref()
+    .and_then(|m| m.permissions)
+    .is_some_and(|p| p.manage_guild());
+
+  if !manager && !is_owner(ctx, interaction).await {
+    let content = "Only members who can manage the server may change snippet permissions";
+    return respond_err(ctx, interaction, "Insufficient permissions", content).await
+  }
+
+  match subcommand(interaction) {
+    Some("show") => show(ctx, interaction, guild).await,
+    Some("grant") => change(ctx, interaction, guild, true).await,
+    Some("revoke") => change(ctx, interaction, guild, false).await,
+    _ => panic!("Invalid subcommand provided to command: {}", interaction.data.name)
+  }
+}
+
+async fn show(ctx: &Context, interaction: &ApplicationCommandInteraction, guild: GuildId) {
+  let settings = {
+    let data = ctx.data.read().await;
+    data.get::<State>().expect("Failed to get state").settings(guild)
+  };
+
+  let mut embed = CreateEmbed::default();
+  embed.title("Snippet permissions")
+    .description("Owners and administrators may always take every action")
+    .colour(super::ACCENT_COLOUR);
+
+  for action in SnippetAction::ALL {
+    let allowed = match settings.roles.get(&action) {
+      Some(roles) if !roles.is_empty() => mention_roles(roles),
+      _ => "Everyone".to_string()
+    };
+
+    embed.field(action.name(), allowed, true);
+  }
+
+  respond_embed(ctx, interaction, &embed, false).await
+}
+
+async fn change(ctx: &Context, interaction: &ApplicationCommandInteraction, guild: GuildId, grant: bool) {
+  let (action, role) = match (arg(interaction, "action"), arg(interaction, "role")) {
+    (
+      CommandDataOptionValue::String(action),
+      CommandDataOptionValue::Role(role)
+    ) => (SnippetAction::from_name(&action).expect("Invalid action provided to command"), role),
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  let allowed = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+    let settings = state.settings_mut(guild);
+
+    if grant == settings.roles.get(&action).is_some_and(|r| r.contains(&role.id)) {
+      Err(match grant {
+        true => format!("{} may already {} snippets", role.name, action.name()),
+        false => format!("{} is not one of the roles that may {} snippets", role.name, action.name())
+      })
+    } else {
+
+      let roles = settings.roles.entry(action).or_default();
+
+      if grant {
+        roles.push(role.id);
+      } else {
+        roles.retain(|r| r != &role.id);
+      }
+
+      let allowed = roles.clone();
+      settings.roles.retain(|_, roles| !roles.is_empty());
+
+      println!("Changed the roles that may {} snippets in guild {guild}", action.name());
+      persistence.mark(Change::Settings { guild });
+
+      Ok(allowed)
+    }
+  };
+
+  let allowed = match allowed {
+    Ok(allowed) => allowed,
+    Err(content) => return respond_err(ctx, interaction, "Failed to change permissions", &content).await
+  };
+
+  let details = match grant {
+    true => format!("Allowed <@&{}> to {} snippets", role.id, action.name()),
+    false => format!("No longer allowed <@&{}> to {} snippets", role.id, action.name())
+  };
+
+  audit_settings(ctx, interaction, details).await;
+  register_guild(ctx, guild).await;
+
+  let content = &match allowed.is_empty() {
+    true => format!("Everyone may now {} snippets", action.name()),
+    false => format!("Only members with {} may now {} snippets", mention_roles(&allowed), action.name())
+  };
+
+  respond_ok(ctx, interaction, "Permissions successfully changed", content).await
+}
+
+fn mention_roles(roles: &[RoleId]) -> String {
+  roles.iter()
+    .map(|r| format!("<@&{r}>"))
+    .collect::<Vec<String>>()
+    .join(", ")
+}
@@ -0,0 +1,288 @@
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::prelude::{GuildId, UserId};
+use serenity::model::Timestamp;
+use serenity::prelude::{Context, TypeMapKey};
+use std::collections::HashMap;
+use crate::storage::{Change, PersistenceContainer};
+use crate::structures::{Embeddable, Platform, Snippet, State};
+use crate::template::{self, TemplateError, Variables};
+
+use super::{arg, audit_settings, is_owner, respond_embed, respond_err, respond_ok, subcommand};
+use super::snippets::get_snippet;
+
+/// The OS the roles of `user` say they use in `guild`, if the guild has
+/// roles for one.
+pub async fn user_platform(ctx: &Context, guild: Option<GuildId>, user: UserId) -> Option<Platform> {
+  let guild = guild?;
+
+  let platforms = {
+    let data = ctx.data.read().await;
+    data.get::<State>().expect("Failed to get state").settings(guild).platforms
+  };
+
+  if platforms.is_empty() {
+    return None
+  }
+
+  let member = guild.member(ctx, user).await.ok()?;
+
+  platforms.into_iter()
+    .find(|(_, roles)| roles.iter().any(|r| member.roles.contains(r)))
+    .map(|(platform, _)| platform)
+}
+
+/// How long the tabs of a shown snippet keep working, in seconds.
+const TABS_LIFETIME: i64 = 7 * 24 * 60 * 60;
+
+/// The snippets shown with tabs, by the id of the command or message that
+/// showed them, which their tabs refer to as snippet ids and locales may not
+/// fit in a custom id.
+pub struct ShownTabs;
+
+impl TypeMapKey for ShownTabs {
+  type Value = HashMap<u64, ShownSnippet>;
+}
+
+/// How a snippet shown with tabs was rendered, so that switching tabs renders
+/// the other sections for the same user and arguments.
+#[derive(Clone)]
+pub struct ShownSnippet {
+  id: String,
+  locale: String,
+  variables: Variables,
+  shown: i64
+}
+
+/// The snippet with the section for `platform`, or for its first OS if it
+/// has no section for `platform`, in which case that OS is returned as the
+/// selected tab.
+fn show_platform(snippet: &Snippet, platform: Option<Platform>) -> (Snippet, Option<Platform>) {
+  match platform.filter(|p| snippet.platforms.contains_key(p)) {
+    Some(platform) => (snippet.for_platform(platform), None),
+    None => match snippet.platforms.keys().next() {
+      Some(first) => (snippet.for_platform(*first), Some(*first)),
+      None => (snippet.clone(), None)
+    }
+  }
+}
+
+/// Renders the `locale` translation of `snippet` with the section for
+/// `platform`, returning it with its buttons. If it is shown with tabs, how
+/// it was rendered is remembered under `key`, the id of the command or
+/// message showing it.
+pub async fn render_for_platform(
+  ctx: &Context,
+  key: u64,
+  snippet: &Snippet,
+  locale: &str,
+  platform: Option<Platform>,
+  variables: Variables
+) -> Result<(Snippet, CreateComponents), TemplateError> {
+  let (shown, tab) = show_platform(&snippet.localized(locale), platform);
+  let rendered = template::render_snippet(&shown, variables.clone()).await?;
+
+  if tab.is_some() {
+    let mut data = ctx.data.write().await;
+    let tabs = data.get_mut::<ShownTabs>().expect("Failed to get shown tabs");
+    let now = Timestamp::now().unix_timestamp();
+
+    tabs.retain(|_, s| now - s.shown < TABS_LIFETIME);
+    tabs.insert(key, ShownSnippet { id: snippet.id.clone(), locale: locale.to_string(), variables, shown: now });
+  }
+
+  let components = tabs(&rendered, tab, key);
+  Ok((rendered, components))
+}
+
+/// The buttons of `snippet`, led by a tab for each of its OS sections if
+/// `tab` is selected. `key` refers the tabs to how the snippet was rendered.
+fn tabs(snippet: &Snippet, tab: Option<Platform>, key: u64) -> CreateComponents {
+  let mut components = CreateComponents::default();
+
+  if let Some(tab) = tab {
+    components.create_action_row(|row| {
+      for platform in snippet.platforms.keys() {
+        row.create_button(|b| b
+          .custom_id(format!("snippet-os:{}:{key}", platform.name()))
+          .label(platform.label())
+          .style(if *platform == tab { ButtonStyle::Primary } else { ButtonStyle::Secondary })
+          .disabled(*platform == tab)
+        );
+      }
+
+      row
+    });
+  }
+
+  snippet.add_buttons(&mut components);
+  components
+}
+
+/// Switches a snippet shown with tabs to the OS of the clicked tab.
+pub(super) async fn change_tab(ctx: &Context, interaction: &MessageComponentInteraction) {
+  let mut parts = interaction.data.custom_id.splitn(3, ':').skip(1);
+
+  let platform = parts.next()
+    .and_then(Platform::from_name)
+    .unwrap_or_else(|| panic!("Invalid custom id provided to component: {}", interaction.data.custom_id));
+
+  // Tabs from before they were remembered have no key
+  let key = parts.next().and_then(|k| k.parse::<u64>().ok());
+
+  // Rendering can fetch from GitHub, which may take longer than Discord waits
+  // for a response
+  if let Err(e) = interaction.defer(ctx).await {
+    return println!("Failed to defer component '{}': {:#?}", interaction.data.custom_id, e)
+  }
+
+  let shown = match key {
+    Some(key) => {
+      let data = ctx.data.read().await;
+      data.get::<ShownTabs>().and_then(|t| t.get(&key)).cloned()
+    },
+    None => None
+  };
+
+  let rendered = match shown {
+    Some(shown) => match get_snippet(ctx, interaction.guild_id, &shown.id).await {
+      Some(snippet) => {
+        let snippet = snippet.localized(&shown.locale).for_platform(platform);
+        template::render_snippet(&snippet, shown.variables).await.map_err(|e| e.to_string())
+      },
+      None => Err("This snippet no longer exists".to_string())
+    },
+    None => Err("These tabs have expired, show the snippet again to switch between them".to_string())
+  };
+
+  let result = match rendered {
+    Ok(snippet) => {
+      let key = key.expect("Tabs were shown without a key");
+
+      interaction.edit_original_interaction_response(ctx, |r| r
+        .set_embed(snippet.embed())
+        .components(|c| {
+          *c = tabs(&snippet, Some(platform), key);
+          c
+        })
+      ).await.map(|_| ())
+    },
+    Err(e) => {
+      interaction.create_followup_message(ctx, |m| m
+        .content(e)
+        .ephemeral(true)
+      ).await.map(|_| ())
+    }
+  };
+
+  if let Err(e) = result {
+    println!("Failed to respond to component '{}': {:#?}", interaction.data.custom_id, e)
+  }
+}
+
+pub(super) async fn snippet_platforms(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let guild = match interaction.guild_id {
+    Some(guild) => guild,
+    None => return respond_err(ctx, interaction, "Failed to change OS roles", "OS roles can only be changed in a server").await
+  };
+
+  let manager = interaction.member.as_ref()
+    .and_then(|m| m.permissions)
+    .is_some_and(|p| p.manage_guild());
+
+  if !manager && !is_owner(ctx, interaction).await {
+    let content = "Only members who can manage the server may change OS roles";
+    return respond_err(ctx, interaction, "Insufficient permissions", content).await
+  }
+
+  match subcommand(interaction) {
+    Some("show") => show(ctx, interaction, guild).await,
+    Some("add") => change(ctx, interaction, guild, true).await,
+    Some("remove") => change(ctx, interaction, guild, false).await,
+    _ => panic!("Invalid subcommand provided to command: {}", interaction.data.name)
+  }
+}
+
+async fn show(ctx: &Context, interaction: &ApplicationCommandInteraction, guild: GuildId) {
+  let settings = {
+    let data = ctx.data.read().await;
+    data.get::<State>().expect("Failed to get state").settings(guild)
+  };
+
+  let mut embed = CreateEmbed::default();
+  embed.title("OS roles")
+    .description("Snippets show the section for the OS of the first of these roles a member has, or every section as tabs")
+    .colour(super::ACCENT_COLOUR);
+
+  for platform in Platform::ALL {
+    let roles = match settings.platforms.get(&platform) {
+      Some(roles) if !roles.is_empty() => roles.iter()
+        .map(|r| format!("<@&{r}>"))
+        .collect::<Vec<String>>()
+        .join(", "),
+      _ => "None".to_string()
+    };
+
+    embed.field(platform.label(), roles, true);
+  }
+
+  respond_embed(ctx, interaction, &embed, false).await
+}
+
+async fn change(ctx: &Context, interaction: &ApplicationCommandInteraction, guild: GuildId, add: bool) {
+  let (platform, role) = match (arg(interaction, "os"), arg(interaction, "role")) {
+    (
+      CommandDataOptionValue::String(platform),
+      CommandDataOptionValue::Role(role)
+    ) => (Platform::from_name(&platform).expect("Invalid OS provided to command"), role),
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  };
+
+  let result = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+    let settings = state.settings_mut(guild);
+
+    if add == settings.platforms.get(&platform).is_some_and(|r| r.contains(&role.id)) {
+      Err(match add {
+        true => format!("{} already marks {} users", role.name, platform.label()),
+        false => format!("{} does not mark {} users", role.name, platform.label())
+      })
+    } else {
+      let roles = settings.platforms.entry(platform).or_default();
+
+      if add {
+        roles.push(role.id);
+      } else {
+        roles.retain(|r| r != &role.id);
+      }
+
+      settings.platforms.retain(|_, roles| !roles.is_empty());
+
+      println!("Changed the roles that mark {} users in guild {guild}", platform.label());
+      persistence.mark(Change::Settings { guild });
+      Ok(())
+    }
+  };
+
+  if let Err(content) = result {
+    return respond_err(ctx, interaction, "Failed to change OS roles", &content).await
+  }
+
+  let details = match add {
+    true => format!("Made <@&{}> mark {} users", role.id, platform.label()),
+    false => format!("Made <@&{}> no longer mark {} users", role.id, platform.label())
+  };
+
+  audit_settings(ctx, interaction, details).await;
+
+  let content = &match add {
+    true => format!("Members with {} will now be shown the {} section of snippets", role.name, platform.label()),
+    false => format!("{} no longer marks {} users", role.name, platform.label())
+  };
+
+  respond_ok(ctx, interaction, "OS roles successfully changed", content).await
+}
@@ -0,0 +1,136 @@
+use serenity::builder::ParseValue;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, ResolvedTarget};
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::prelude::MessageId;
+use serenity::prelude::Context;
+use crate::search;
+use crate::structures::{Embeddable, State, Usage, UsageCounts, UsageSource};
+use crate::template::Variables;
+
+use super::browse::truncate;
+use super::{platforms, record_usage};
+use super::snippets::get_snippet;
+
+/// The name of the message context menu command.
+pub const REPLY_COMMAND: &str = "Reply with snippet";
+
+/// Shows the user a menu of snippets to reply to the targeted message with,
+/// those most related to the message first.
+pub(super) async fn reply_with_snippet(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let message = match interaction.data.target() {
+    Some(ResolvedTarget::Message(message)) => message,
+    _ => panic!("Invalid target provided to command: {}", interaction.data.name)
+  };
+
+  let choices: Vec<(String, String)> = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+    let uses = data.get::<UsageCounts>().and_then(|c| c.get(&interaction.guild_id));
+
+    search::related(state.snippets(interaction.guild_id), &message.content, uses).into_iter()
+      .take(25)
+      .map(|s| (truncate(&s.title, 100), s.id.clone()))
+      .collect()
+  };
+
+  let result = interaction.create_interaction_response(ctx, |r| r
+    .kind(InteractionResponseType::ChannelMessageWithSource)
+    .interaction_response_data(|d| {
+      d.ephemeral(true);
+
+      if choices.is_empty() {
+        return d.content("There are no snippets to reply with")
+      }
+
+      d.content(format!("Choose a snippet to reply to {} with", message.author.name))
+        .components(|c| c.create_action_row(|row| row
+          .create_select_menu(|menu| menu
+            .custom_id(format!("snippet-reply:{}", message.id))
+            .placeholder("Reply with a snippet")
+            .options(|options| {
+              for (title, id) in choices {
+                options.create_option(|o| o
+                  .label(title)
+                  .description(truncate(&id, 100))
+                  .value(id)
+                );
+              }
+
+              options
+            })
+          )
+        ))
+    })
+  ).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to interaction '{}': {:#?}", interaction.data.name, e)
+  }
+}
+
+/// Replies to the message the menu was opened for with the chosen snippet,
+/// mentioning its author.
+pub(super) async fn reply(ctx: &Context, interaction: &MessageComponentInteraction) {
+  let message = interaction.data.custom_id.split_once(':')
+    .and_then(|(_, id)| id.parse::<u64>().ok())
+    .map(MessageId)
+    .unwrap_or_else(|| panic!("Invalid custom id provided to component: {}", interaction.data.custom_id));
+
+  let id = interaction.data.values.first().cloned().unwrap_or_default();
+
+  // Rendering can fetch from GitHub, which may take longer than Discord waits
+  // for a response
+  if let Err(e) = interaction.defer(ctx).await {
+    return println!("Failed to defer component '{}': {:#?}", interaction.data.custom_id, e)
+  }
+
+  let rendered = match get_snippet(ctx, interaction.guild_id, &id).await {
+    Some(snippet) => {
+      let target = interaction.channel_id.message(ctx, message).await.ok();
+      let platform = match target {
+        Some(target) => platforms::user_platform(ctx, interaction.guild_id, target.author.id).await,
+        None => None
+      };
+
+      let variables = Variables::new(interaction.user.id, interaction.channel_id, Vec::new());
+
+      platforms::render_for_platform(ctx, interaction.id.0, &snippet, &interaction.locale, platform, variables).await
+        .map_err(|e| e.to_string())
+    },
+    None => Err("This snippet no longer exists".to_string())
+  };
+
+  let sent = match rendered {
+    Ok((snippet, components)) => {
+      let result = interaction.channel_id.send_message(ctx, |m| m
+        .reference_message((interaction.channel_id, message))
+        .allowed_mentions(|a| a.replied_user(true).parse(ParseValue::Users))
+        .set_embed(snippet.embed())
+        .set_components(components)
+      ).await;
+
+      result.map(|_| snippet.id).map_err(|e| format!("Unable to reply to the message: {e}"))
+    },
+    Err(e) => Err(e)
+  };
+
+  let content = match &sent {
+    Ok(id) => format!("Replied with '{id}'"),
+    Err(e) => e.clone()
+  };
+
+  let result = interaction.edit_original_interaction_response(ctx, |r| r
+    .content(content)
+    .components(|c| c)
+  ).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to component '{}': {:#?}", interaction.data.custom_id, e)
+  }
+
+  if let Ok(id) = sent {
+    let usage = Usage::new(interaction.user.id, interaction.guild_id, interaction.channel_id, id, UsageSource::Reply);
+    record_usage(ctx, usage).await
+  }
+}
@@ -1,36 +1,106 @@
 use core::panic;
-use serenity::builder::{CreateEmbed, CreateApplicationCommandOption, CreateApplicationCommand};
-use serenity::json::Value;
-use serenity::model::prelude::command::CommandOptionType;
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::prelude::GuildId;
 use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::prelude::interaction::autocomplete::AutocompleteInteraction;
 use serenity::prelude::Context;
-use crate::structures::{State, Snippet, Embeddable};
+use serenity::utils::Colour;
+use crate::search;
+use crate::template::{self, Variables};
+use crate::storage::{self, Change, PersistenceContainer};
+use crate::structures::{State, Platform, Snippet, SnippetAction, SnippetField, SnippetLink, SnippetVariant, Embeddable, Usage, UsageCounts, UsageSource};
 use crate::commands::{arg, respond_ok};
 
-use super::{respond_err, respond_embed, arg_opt};
+use super::{respond_err, respond_embed, arg_opt, audit, is_owner, permissions, platforms, record_usage};
 
-pub(super) fn sync_snippets(state: &State, command: &mut CreateApplicationCommand) {
-  let mut id_option = CreateApplicationCommandOption::default();
-  id_option.name("id")
-    .description("The snippet's id")
-    .kind(CommandOptionType::String)
-    .required(true);
+/// Why a snippet could not be saved, as the title and description of the
+/// error shown to the user.
+pub(super) type Failure = (&'static str, String);
 
-  for snippet in state.snippets.iter().take(25) {
-    let name = format!("{}: {}", snippet.id, snippet.title);
-    id_option.add_string_choice(name, snippet.id.clone());
-  }
+/// The number of revisions shown by `snippet-history`.
+const HISTORY_LENGTH: usize = 20;
+
+/// The maximum length of an autocomplete choice's name.
+const CHOICE_LENGTH: usize = 100;
+
+pub(super) async fn autocomplete_id(ctx: &Context, interaction: &AutocompleteInteraction, query: &str) {
+  let choices: Vec<(String, String)> = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+
+    let uses = data.get::<UsageCounts>().and_then(|c| c.get(&interaction.guild_id));
+
+    search::rank(state.snippets(interaction.guild_id), query, uses).into_iter()
+      .take(25)
+      .map(|s| (format!("{}: {}", s.id, s.title).chars().take(CHOICE_LENGTH).collect(), s.id.clone()))
+      .collect()
+  };
+
+  let result = interaction.create_autocomplete_response(ctx, |r| {
+    for (name, id) in choices {
+      r.add_string_choice(name, id);
+    }
+
+    r
+  }).await;
 
-  insert_option(command, 0, id_option);
+  if let Err(e) = result {
+    println!("Failed to respond to autocomplete for '{}': {:#?}", interaction.data.name, e)
+  }
 }
 
 pub(super) async fn snippet(ctx: &Context, interaction: &ApplicationCommandInteraction) {
   match arg(interaction, "id") {
     CommandDataOptionValue::String(id) => {
-      if let Some(snippet) = get_snippet(ctx, &id).await {
-        let embed = snippet.embed();
+      if let Some(snippet) = get_snippet(ctx, interaction.guild_id, &id).await {
+        let locale = match arg_opt(interaction, "language") {
+          Some(CommandDataOptionValue::String(language)) if language.eq_ignore_ascii_case("default") => language,
+          Some(CommandDataOptionValue::String(language)) => match snippet.find_variant(&language) {
+            Some(locale) => locale.to_string(),
+            None => {
+              let content = &format!("The snippet '{id}' has no '{language}' translation, {}", list_variants(&snippet));
+              return respond_err(ctx, interaction, "Failed to show snippet", content).await
+            }
+          },
+          _ => interaction.locale.clone()
+        };
 
-        respond_embed(ctx, interaction, &embed, false).await;
+        let platform = match arg_opt(interaction, "os") {
+          Some(CommandDataOptionValue::String(os)) => Platform::from_name(&os),
+          _ => {
+            let user = match arg_opt(interaction, "user") {
+              Some(CommandDataOptionValue::User(user, _)) => user.id,
+              _ => interaction.user.id
+            };
+
+            platforms::user_platform(ctx, interaction.guild_id, user).await
+          }
+        };
+
+        let args = match arg_opt(interaction, "args") {
+          Some(CommandDataOptionValue::String(args)) => template::parse_args(&args),
+          _ => Vec::new()
+        };
+
+        let variables = Variables::new(interaction.user.id, interaction.channel_id, args);
+
+        match platforms::render_for_platform(ctx, interaction.id.0, &snippet, &locale, platform, variables).await {
+          Ok((snippet, components)) => {
+            let result = interaction.create_followup_message(ctx, |r| r
+              .add_embed(snippet.embed())
+              .set_components(components)
+            ).await;
+
+            if let Err(e) = result {
+              return println!("Failed to respond to interaction '{}': {:#?}", interaction.data.name, e)
+            }
+
+            let usage = Usage::new(interaction.user.id, interaction.guild_id, interaction.channel_id, snippet.id, UsageSource::SlashCommand);
+            record_usage(ctx, usage).await
+          },
+          Err(e) => respond_err(ctx, interaction, "Failed to show snippet", &e.to_string()).await
+        }
       } else {
         respond_err(ctx, interaction, "Failed to find snippet", &format!("Failed to find the snippet '{id}'")).await;
       }
@@ -39,113 +109,316 @@ pub(super) async fn snippet(ctx: &Context, interaction: &ApplicationCommandInter
   }
 }
 
+/// Suggests the translations of the snippet chosen in the `id` option.
+pub(super) async fn autocomplete_language(ctx: &Context, interaction: &AutocompleteInteraction, query: &str) {
+  let id = interaction.data.options.iter()
+    .find(|o| o.name == "id")
+    .and_then(|o| o.value.as_ref())
+    .and_then(|v| v.as_str())
+    .unwrap_or_default();
+
+  let locales: Vec<String> = match get_snippet(ctx, interaction.guild_id, id).await {
+    Some(snippet) => std::iter::once("default".to_string())
+      .chain(snippet.variants.into_keys())
+      .filter(|l| l.to_lowercase().starts_with(&query.to_lowercase()))
+      .take(25)
+      .collect(),
+    None => Vec::new()
+  };
+
+  let result = interaction.create_autocomplete_response(ctx, |r| {
+    for locale in locales {
+      r.add_string_choice(&locale, &locale);
+    }
+
+    r
+  }).await;
+
+  if let Err(e) = result {
+    println!("Failed to respond to autocomplete for '{}': {:#?}", interaction.data.name, e)
+  }
+}
+
+/// Describes the translations `snippet` has, for when one was not found.
+fn list_variants(snippet: &Snippet) -> String {
+  match snippet.variants.is_empty() {
+    true => "it has no translations".to_string(),
+    false => format!("it has {}", snippet.variants.keys().cloned().collect::<Vec<String>>().join(", "))
+  }
+}
+
 pub(super) async fn edit_snippet(ctx: &Context, interaction: &ApplicationCommandInteraction) {
-  let id = arg(interaction, "id");
-  let title = arg_opt(interaction, "title");
-  let content = arg_opt(interaction, "content");
+  let id = match arg(interaction, "id") {
+    CommandDataOptionValue::String(id) => id,
+    _ => panic!("Invalid arguments provided to command: {}", &interaction.data.name)
+  };
 
-  if let CommandDataOptionValue::String(id) = id {
-    {
-      let mut data = ctx.data.write().await;
-      let state = data.get_mut::<State>().expect("Failed to get state");
+  let title = match arg_opt(interaction, "title") {
+    Some(CommandDataOptionValue::String(title)) => Some(title),
+    _ => None
+  };
 
-      let snippet = state.snippets.iter_mut().find(|s| s.id.eq(&id));
+  let content = match arg_opt(interaction, "content") {
+    Some(CommandDataOptionValue::String(content)) => Some(content.replace(r#"\n"#, "\n")),
+    _ => None
+  };
 
-      if let Some(snippet) = snippet {
-        if let Some(CommandDataOptionValue::String(title)) = title {
-          snippet.title = title;
-        }
+  match save_edit(ctx, interaction, &id, title, content).await {
+    Ok(embed) => respond_embed(ctx, interaction, &embed, false).await,
+    Err((title, content)) => respond_err(ctx, interaction, title, &content).await
+  }
+}
 
-        if let Some(CommandDataOptionValue::String(content)) = content {
-          snippet.content = content;
-        }
+/// Edits the snippet `id` as `/edit-snippet` would, with `title` and
+/// `content` in place of the command's own, creating the snippet if it does
+/// not exist and both are given. The translation given by the `language`
+/// option is edited instead of the default title and content if there is one.
+pub(super) async fn save_edit(
+  ctx: &Context,
+  interaction: &ApplicationCommandInteraction,
+  id: &str,
+  title: Option<String>,
+  content: Option<String>
+) -> Result<CreateEmbed, Failure> {
+  let guild = interaction.guild_id;
+  let owner = is_owner(ctx, interaction).await;
+
+  let language = match arg_opt(interaction, "language") {
+    Some(CommandDataOptionValue::String(language)) if !language.eq_ignore_ascii_case("default") => Some(language.trim().to_string()),
+    _ => None
+  };
+
+  let exists = {
+    let data = ctx.data.read().await;
+    data.get::<State>().expect("Failed to get state").find_scope(guild, id).is_some()
+  };
+
+  // Editing a snippet that does not exist yet creates it
+  if !exists {
+    require(ctx, interaction, SnippetAction::Create).await?;
+  }
 
-        println!("Snippet edited '{}: {}'", &snippet.title, &snippet.content);
+  let (id, before, after) = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
 
-        state.write()
-      } else {
-        match (title, content) {
-          (
-            Some(CommandDataOptionValue::String(title)),
-            Some(CommandDataOptionValue::String(content))
-          ) => {
-            let snippet = Snippet {
-              id: id.clone(),
-              title: title.clone(),
-              content: content.replace(r#"\n"#, "\n")
-            };
+    let scope = state.find_scope(guild, id);
+
+    if scope == Some(None) && storage::is_read_only() {
+      return Err(read_only(id))
+    }
 
-            println!("New snippet created '{}: {}'", id, title);
+    if scope == Some(None) && !owner {
+      return Err(global_denied(id))
+    }
 
-            state.snippets.push(snippet);
-            state.write()
-          },
-          _ => {
-            let title = "Failed to edit snippet";
-            let content = &format!("The snippet '{}' does not exist", &id);
-            return respond_err(ctx, interaction, title, content).await
+    let snippet = scope.and_then(|scope| state.scope_mut(scope).iter_mut().find(|s| s.matches(id)));
+
+    if let Some(snippet) = snippet {
+      let mut updated = snippet.clone();
+
+      match &language {
+        Some(language) => {
+          if let Err(e) = edit_variant(&mut updated, language, title, content) {
+            return Err(("Failed to edit snippet", e))
+          }
+        },
+        None => {
+          let title = title.unwrap_or_else(|| snippet.title.clone());
+          let content = content.unwrap_or_else(|| snippet.content.clone());
+
+          if title != snippet.title || content != snippet.content {
+            updated.revise(interaction.user.id, title, content);
           }
         }
       }
+
+      if let Err(e) = apply_options(interaction, &mut updated) {
+        return Err(("Failed to edit snippet", e))
+      }
+
+      let before = snippet.snapshot();
+      *snippet = updated;
+
+      println!("Snippet edited '{}: {}'", &snippet.title, &snippet.content);
+
+      let scope = scope.expect("Snippet was found without a scope");
+      persistence.mark(Change::Snippet { scope, id: snippet.id.clone() });
+      (snippet.id.clone(), Some(before), snippet.snapshot())
+    } else {
+      match (title, content) {
+        (Some(title), Some(content)) if language.is_none() => {
+          let mut snippet = Snippet::new(id.to_string(), title.clone(), content);
+
+          if let Err(e) = apply_options(interaction, &mut snippet) {
+            return Err(("Failed to create snippet", e))
+          }
+
+          println!("New snippet created '{}: {}'", id, title);
+
+          state.scope_mut(guild).push(snippet.clone());
+          persistence.mark(Change::Snippet { scope: guild, id: snippet.id.clone() });
+          (id.to_string(), None, snippet)
+        },
+        _ => return Err(("Failed to edit snippet", format!("The snippet '{id}' does not exist")))
+      }
     }
+  };
 
-    super::update_commands(ctx).await;
+  audit(ctx, interaction, Some(&id), before, Some(after)).await;
 
-    let mut embed = get_snippet(ctx, &id).await
-      .expect("Failed to get snippet for recently modified snippet")
-      .embed();
+  let snippet = get_snippet(ctx, guild, &id).await
+    .expect("Failed to get snippet for recently modified snippet");
 
-    embed.colour(super::OK_COLOUR);
+  let mut embed = match &language {
+    Some(language) => snippet.localized(language).embed(),
+    None => snippet.embed()
+  };
 
-    respond_embed(ctx, interaction, &embed, false).await;
+  embed.colour(super::OK_COLOUR);
+
+  Ok(embed)
+}
+
+/// Fails if the user of `interaction` may not take `action` in its guild, for
+/// when a command turns out to take another action than the one it was
+/// checked for.
+async fn require(ctx: &Context, interaction: &ApplicationCommandInteraction, action: SnippetAction) -> Result<(), Failure> {
+  let guild = match interaction.guild_id {
+    Some(guild) => guild,
+    None => return Ok(())
+  };
+
+  match permissions::denied(ctx, guild, interaction.user.id, interaction.member.as_ref(), action).await {
+    Some(roles) => Err(("Insufficient permissions", permissions::denial(action, &roles))),
+    None => Ok(())
   }
 }
 
-pub(super) async fn create_snippet(ctx: &Context, interaction: &ApplicationCommandInteraction) {
-  let id = arg(interaction, "id");
-  let title = arg(interaction, "title");
-  let content = arg(interaction, "content");
+/// Sets the title and content of the `language` translation of `snippet`,
+/// keeping the current ones where `None`. Content of '-' removes it.
+fn edit_variant(snippet: &mut Snippet, language: &str, title: Option<String>, content: Option<String>) -> Result<(), String> {
+  let locale = snippet.variants.keys()
+    .find(|k| k.eq_ignore_ascii_case(language))
+    .cloned()
+    .unwrap_or_else(|| language.to_string());
 
-  match (id, title, content) {
+  if content.as_deref().map(str::trim) == Some("-") {
+    return match snippet.variants.remove(&locale) {
+      Some(_) => Ok(()),
+      None => Err(format!("The snippet '{}' has no '{language}' translation", snippet.id))
+    }
+  }
+
+  let current = snippet.variants.get(&locale);
+  let title = title.or_else(|| current.map(|v| v.title.clone()));
+  let content = content.or_else(|| current.map(|v| v.content.clone()));
+
+  match (title, content) {
+    (Some(title), Some(content)) => {
+      snippet.variants.insert(locale, SnippetVariant { title, content });
+      Ok(())
+    },
+    _ => Err(format!("The snippet '{}' has no '{language}' translation yet, so both a title and content are needed", snippet.id))
+  }
+}
+
+pub(super) async fn create_snippet(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let (id, title, content) = match (arg(interaction, "id"), arg(interaction, "title"), arg(interaction, "content")) {
     (
       CommandDataOptionValue::String(id),
       CommandDataOptionValue::String(title),
       CommandDataOptionValue::String(content)
-    ) => {
-      let embed = {
-        let mut data = ctx.data.write().await;
-        let state = data.get_mut::<State>().expect("Failed to get state");
+    ) => (id, title, content.replace(r#"\n"#, "\n")),
+    _ => panic!("Invalid arguments provided to command: {}", &interaction.data.name)
+  };
 
-        if let Some(snippet) = state.snippets.iter().position(|s| s.id.eq(&id)) {
-          state.snippets.remove(snippet);
-        }
+  match save_create(ctx, interaction, &id, title, content).await {
+    Ok(embed) => respond_embed(ctx, interaction, &embed, false).await,
+    Err((title, content)) => respond_err(ctx, interaction, title, &content).await
+  }
+}
 
-        let snippet = Snippet {
-          id: id.clone(),
-          title: title.clone(),
-          content: content.replace(r#"\n"#, "\n")
-        };
+/// Creates or replaces the snippet `id` as `/create-snippet` would, with
+/// `title` and `content` in place of the command's own.
+pub(super) async fn save_create(
+  ctx: &Context,
+  interaction: &ApplicationCommandInteraction,
+  id: &str,
+  title: String,
+  content: String
+) -> Result<CreateEmbed, Failure> {
+  let global = matches!(arg_opt(interaction, "global"), Some(CommandDataOptionValue::Boolean(true)));
+  let scope = if global { None } else { interaction.guild_id };
+
+  if scope.is_none() && storage::is_read_only() {
+    return Err(read_only(id))
+  }
+
+  if scope.is_none() && !is_owner(ctx, interaction).await {
+    return Err(global_denied(id))
+  }
+
+  let exists = {
+    let data = ctx.data.read().await;
+    data.get::<State>().expect("Failed to get state").scope(scope).iter().any(|s| s.id == id)
+  };
 
-        println!("New snippet created '{}: {}'", id, title);
+  // Creating a snippet that already exists replaces it
+  if exists {
+    require(ctx, interaction, SnippetAction::Edit).await?;
+  }
+
+  let (embed, before, after) = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+
+    if let Some(conflict) = state.find_conflict(scope, id, id) {
+      return Err(("Failed to create snippet", format!("'{id}' is already an alias of the snippet '{}'", conflict.id)))
+    }
 
-        let mut embed = snippet.embed();
-        embed.colour(super::OK_COLOUR);
+    let snippets = state.scope_mut(scope);
 
-        state.snippets.push(snippet);
-        state.write();
+    let (snippet, before) = match snippets.iter_mut().find(|s| s.id == id) {
+      Some(snippet) => {
+        let mut updated = snippet.clone();
+        updated.revise(interaction.user.id, title.clone(), content);
 
-        if state.snippets.len() > 25 {
-          embed.field("Warning", "There are more than 25 snippets, some may not appear in the snippet list.", false);
+        if let Err(e) = apply_options(interaction, &mut updated) {
+          return Err(("Failed to create snippet", e))
         }
 
-        embed
-      };
+        let before = snippet.snapshot();
+        *snippet = updated;
+        (snippet.clone(), Some(before))
+      },
+      None => {
+        let mut snippet = Snippet::new(id.to_string(), title.clone(), content);
 
-      super::update_commands(ctx).await;
-      respond_embed(ctx, interaction, &embed, false).await;
-    },
-    _ => panic!("Invalid arguments provided to command: {}", &interaction.data.name)
-  }
+        if let Err(e) = apply_options(interaction, &mut snippet) {
+          return Err(("Failed to create snippet", e))
+        }
+
+        snippets.push(snippet.clone());
+        (snippet, None)
+      }
+    };
+
+    println!("New snippet created '{}: {}'", id, title);
+
+    let mut embed = snippet.embed();
+    embed.colour(super::OK_COLOUR);
+
+    persistence.mark(Change::Snippet { scope, id: snippet.id.clone() });
+
+    (embed, before, snippet.snapshot())
+  };
+
+  audit(ctx, interaction, Some(id), before, Some(after)).await;
+
+  Ok(embed)
 }
 
 pub(super) async fn remove_snippet(ctx: &Context, interaction: &ApplicationCommandInteraction) {
@@ -155,10 +428,26 @@ pub(super) async fn remove_snippet(ctx: &Context, interaction: &ApplicationComma
     CommandDataOptionValue::String(id) => {
       println!("Removing snippet '{id}'");
 
-      match get_snippet(ctx, &id).await {
+      let guild = interaction.guild_id;
+      let scope = {
+        let data = ctx.data.read().await;
+        let state = data.get::<State>().expect("Failed to get state");
+        state.find_scope(guild, &id)
+      };
+
+      if scope == Some(None) && storage::is_read_only() {
+        return respond_read_only(ctx, interaction, &id).await
+      }
+
+      if scope == Some(None) && !is_owner(ctx, interaction).await {
+        return respond_global_denied(ctx, interaction, &id).await
+      }
+
+      match get_snippet(ctx, guild, &id).await {
         Some(snippet) => {
-          rm_snippet(ctx, &snippet).await;
-          super::update_commands(ctx).await;
+          let scope = scope.expect("Snippet was found without a scope");
+          rm_snippet(ctx, scope, &snippet).await;
+          audit(ctx, interaction, Some(&snippet.id), Some(snippet.snapshot()), None).await;
 
           let title = &"Snippet successfully removed";
           let content = &&format!("Removed snippet '{}: {}'", snippet.id, snippet.title);
@@ -180,8 +469,13 @@ pub(super) async fn export_snippet(ctx: &Context, interaction: &ApplicationComma
 
   match id {
     CommandDataOptionValue::String(id) => {
-      let snippet = get_snippet(ctx, &id).await
-        .expect("Failed to get snippet");
+      let snippet = match get_snippet(ctx, interaction.guild_id, &id).await {
+        Some(snippet) => snippet,
+        None => {
+          let content = &format!("The snippet '{id}' does not exist");
+          return respond_err(ctx, interaction, "Failed to export snippet", content).await
+        }
+      };
 
       let result = interaction.create_followup_message(ctx, |r| r
         .content(format!("```{}```", &snippet.content.replace("\n", r#"\n"#)))
@@ -196,44 +490,303 @@ pub(super) async fn export_snippet(ctx: &Context, interaction: &ApplicationComma
   }
 }
 
+pub(super) async fn snippet_history(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  match arg(interaction, "id") {
+    CommandDataOptionValue::String(id) => {
+      let snippet = match get_snippet(ctx, interaction.guild_id, &id).await {
+        Some(snippet) => snippet,
+        None => {
+          let content = &format!("The snippet '{id}' does not exist");
+          return respond_err(ctx, interaction, "Failed to get snippet history", content).await
+        }
+      };
+
+      let description = if snippet.revisions.is_empty() {
+        "This snippet has never been changed".to_string()
+      } else {
+        let mut lines = snippet.revisions.iter()
+          .rev()
+          .map(|r| format!("**{}.** <t:{}:f> by <@{}>: {}", r.number, r.timestamp.unix_timestamp(), r.author, r.title))
+          .take(HISTORY_LENGTH)
+          .collect::<Vec<String>>();
+
+        if snippet.revisions.len() > HISTORY_LENGTH {
+          lines.push(format!("*{} older revisions not shown*", snippet.revisions.len() - HISTORY_LENGTH));
+        }
+
+        lines.join("\n")
+      };
+
+      let mut embed = CreateEmbed::default();
+      embed.title(format!("History of '{}'", snippet.id))
+        .description(description)
+        .footer(|f| f.text("Each revision holds the title and content it replaced"))
+        .colour(super::ACCENT_COLOUR);
+
+      respond_embed(ctx, interaction, &embed, false).await;
+    },
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  }
+}
+
+pub(super) async fn snippet_rollback(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  match (arg(interaction, "id"), arg(interaction, "revision")) {
+    (
+      CommandDataOptionValue::String(id),
+      CommandDataOptionValue::Integer(revision)
+    ) => {
+      let guild = interaction.guild_id;
+      let scope = {
+        let data = ctx.data.read().await;
+        let state = data.get::<State>().expect("Failed to get state");
+        state.find_scope(guild, &id)
+      };
+
+      let scope = match scope {
+        Some(None) if storage::is_read_only() => {
+          return respond_read_only(ctx, interaction, &id).await
+        },
+        Some(None) if !is_owner(ctx, interaction).await => {
+          return respond_global_denied(ctx, interaction, &id).await
+        },
+        Some(scope) => scope,
+        None => {
+          let content = &format!("The snippet '{id}' does not exist");
+          return respond_err(ctx, interaction, "Failed to roll back snippet", content).await
+        }
+      };
+
+      let embed = {
+        let mut data = ctx.data.write().await;
+        let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+        let state = data.get_mut::<State>().expect("Failed to get state");
+
+        let snippet = state.scope_mut(scope).iter_mut()
+          .find(|s| s.matches(&id))
+          .expect("Snippet was found without a scope");
+
+        let target = snippet.revisions.iter()
+          .find(|r| i64::try_from(r.number) == Ok(revision))
+          .cloned();
+
+        target.map(|target| {
+          println!("Rolling back snippet '{id}' to revision {revision}");
+          let before = snippet.snapshot();
+          snippet.revise(interaction.user.id, target.title, target.content);
+          persistence.mark(Change::Snippet { scope, id: snippet.id.clone() });
+
+          (snippet.embed(), before, snippet.snapshot())
+        })
+      };
+
+      match embed {
+        Some((mut embed, before, after)) => {
+          let id = after.id.clone();
+          audit(ctx, interaction, Some(&id), Some(before), Some(after)).await;
+
+          embed.colour(super::OK_COLOUR);
+          respond_embed(ctx, interaction, &embed, false).await
+        },
+        None => {
+          let content = &format!("The snippet '{id}' has no revision {revision}");
+          respond_err(ctx, interaction, "Failed to roll back snippet", content).await
+        }
+      }
+    },
+    _ => panic!("Invalid arguments provided to command: {}", interaction.data.name)
+  }
+}
+
 impl Embeddable for Snippet {
   fn embed(&self) -> CreateEmbed {
-    CreateEmbed::default()
-      .title(&self.title)
+    let mut embed = CreateEmbed::default();
+    embed.title(&self.title)
       .description(&self.content)
-      .colour(super::ACCENT_COLOUR)
-      .clone()
+      .colour(self.colour.map(Colour).unwrap_or(super::ACCENT_COLOUR));
+
+    for field in &self.fields {
+      embed.field(&field.name, &field.value, field.inline);
+    }
+
+    if let Some(image) = &self.image {
+      embed.image(image);
+    }
+
+    if let Some(thumbnail) = &self.thumbnail {
+      embed.thumbnail(thumbnail);
+    }
+
+    if let Some(footer) = &self.footer {
+      embed.footer(|f| f.text(footer));
+    }
+
+    embed
   }
 }
 
-async fn get_snippet(ctx: &Context, id: &str) -> Option<Snippet> {
+impl Snippet {
+  /// Adds the snippet's link buttons to `components`, five to a row.
+  pub fn add_buttons(&self, components: &mut CreateComponents) {
+    for links in self.links.chunks(5) {
+      components.create_action_row(|row| {
+        for link in links {
+          row.create_button(|b| b
+            .label(&link.label)
+            .url(&link.url)
+            .style(ButtonStyle::Link)
+          );
+        }
+
+        row
+      });
+    }
+  }
+}
+
+/// Applies every optional property given to `interaction` to `snippet` and
+/// checks that the result can still be shown.
+fn apply_options(interaction: &ApplicationCommandInteraction, snippet: &mut Snippet) -> Result<(), String> {
+  apply_labels(interaction, snippet);
+  apply_appearance(interaction, snippet)?;
+  apply_platforms(interaction, snippet);
+  snippet.validate()
+}
+
+/// Sets the OS sections given to `interaction`, where '-' removes one.
+fn apply_platforms(interaction: &ApplicationCommandInteraction, snippet: &mut Snippet) {
+  for platform in Platform::ALL {
+    if let Some(CommandDataOptionValue::String(section)) = arg_opt(interaction, platform.name()) {
+      let section = section.trim().replace(r#"\n"#, "\n");
+
+      if section.is_empty() || section == "-" {
+        snippet.platforms.remove(&platform);
+      } else {
+        snippet.platforms.insert(platform, section);
+      }
+    }
+  }
+}
+
+/// Sets the embed appearance given to `interaction`, where '-' removes a
+/// property. Fields and links are given one per line as `name | value` and
+/// `label | url`, where a field may end with `| inline`.
+fn apply_appearance(interaction: &ApplicationCommandInteraction, snippet: &mut Snippet) -> Result<(), String> {
+  let text = |name: &'static str| match arg_opt(interaction, name) {
+    Some(CommandDataOptionValue::String(value)) => {
+      let value = value.trim().replace(r#"\n"#, "\n");
+      Some((!value.is_empty() && value != "-").then_some(value))
+    },
+    _ => None
+  };
+
+  if let Some(colour) = text("colour") {
+    snippet.colour = colour.map(|c| parse_colour(&c)).transpose()?;
+  }
+
+  if let Some(image) = text("image") {
+    snippet.image = image;
+  }
+
+  if let Some(thumbnail) = text("thumbnail") {
+    snippet.thumbnail = thumbnail;
+  }
+
+  if let Some(footer) = text("footer") {
+    snippet.footer = footer;
+  }
+
+  if let Some(fields) = text("fields") {
+    snippet.fields = fields.unwrap_or_default().lines()
+      .filter(|l| !l.trim().is_empty())
+      .map(|line| {
+        let mut parts = line.split('|').map(str::trim);
+        let name = parts.next().unwrap_or_default().to_string();
+        let value = parts.next().ok_or_else(|| format!("The field '{line}' has no value, expected `name | value`"))?;
+        let inline = parts.next().is_some_and(|p| p.eq_ignore_ascii_case("inline"));
+
+        Ok(SnippetField { name, value: value.to_string(), inline })
+      })
+      .collect::<Result<Vec<SnippetField>, String>>()?;
+  }
+
+  if let Some(links) = text("links") {
+    snippet.links = links.unwrap_or_default().lines()
+      .filter(|l| !l.trim().is_empty())
+      .map(|line| match line.rsplit_once('|') {
+        Some((label, url)) => Ok(SnippetLink { label: label.trim().to_string(), url: url.trim().to_string() }),
+        None => Err(format!("The link '{line}' has no URL, expected `label | url`"))
+      })
+      .collect::<Result<Vec<SnippetLink>, String>>()?;
+  }
+
+  Ok(())
+}
+
+fn parse_colour(colour: &str) -> Result<u32, String> {
+  match colour.strip_prefix('#') {
+    Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+      Ok(u32::from_str_radix(hex, 16).expect("Failed to parse hex digits"))
+    },
+    _ => Err(format!("The colour '{colour}' is not a hexadecimal colour such as #ff00ff"))
+  }
+}
+
+/// Sets the category and tags given to `interaction`, where '-' removes them.
+fn apply_labels(interaction: &ApplicationCommandInteraction, snippet: &mut Snippet) {
+  if let Some(CommandDataOptionValue::String(category)) = arg_opt(interaction, "category") {
+    let category = category.trim();
+    snippet.category = (!category.is_empty() && category != "-").then(|| category.to_string());
+  }
+
+  if let Some(CommandDataOptionValue::String(tags)) = arg_opt(interaction, "tags") {
+    snippet.tags = Vec::new();
+
+    for tag in tags.split(',').map(|t| t.trim().to_lowercase()) {
+      if !tag.is_empty() && tag != "-" && !snippet.tags.contains(&tag) {
+        snippet.tags.push(tag);
+      }
+    }
+  }
+}
+
+pub(super) async fn get_snippet(ctx: &Context, guild: Option<GuildId>, id: &str) -> Option<Snippet> {
   let data = ctx.data.read().await;
   let state = data.get::<State>().expect("Failed to get state");
 
-  state.snippets.iter()
-    .find(|s| s.id.eq(id))
-    .cloned()
+  state.get_snippet(guild, id).cloned()
 }
 
-async fn rm_snippet(ctx: &Context, snippet: &Snippet) {
+async fn rm_snippet(ctx: &Context, scope: Option<GuildId>, snippet: &Snippet) {
   let mut data = ctx.data.write().await;
+  let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
   let state = data.get_mut::<State>()
     .expect("Failed to get state");
 
-  let index = state.snippets.iter()
+  let snippets = state.scope_mut(scope);
+  let index = snippets.iter()
     .position(|s| s.id == snippet.id)
     .expect("Snippet was not found in vec");
 
   println!("Removing snippet '{}: {}'", snippet.id, snippet.title);
-  state.snippets.remove(index);
-  state.write();
+  snippets.remove(index);
+
+  persistence.mark(Change::Snippet { scope, id: snippet.id.clone() });
+}
+
+pub(super) async fn respond_global_denied(ctx: &Context, interaction: &ApplicationCommandInteraction, id: &str) {
+  let (title, content) = global_denied(id);
+  respond_err(ctx, interaction, title, &content).await
 }
 
-fn insert_option(command: &mut CreateApplicationCommand, index: usize, option: CreateApplicationCommandOption) -> &mut CreateApplicationCommand {
-  let new_option = serenity::json::hashmap_to_json_map(option.0);
-  let options = command.0.entry("options").or_insert_with(|| Value::from(Vec::<Value>::new()));
-  let opt_arr = options.as_array_mut().expect("Must be an array");
-  opt_arr.insert(index, Value::from(new_option));
+pub(super) async fn respond_read_only(ctx: &Context, interaction: &ApplicationCommandInteraction, id: &str) {
+  let (title, content) = read_only(id);
+  respond_err(ctx, interaction, title, &content).await
+}
+
+pub(super) fn global_denied(id: &str) -> Failure {
+  ("Insufficient permissions", format!("The snippet '{id}' is shared with every server, only the bot owner may change it"))
+}
 
-  command
+pub(super) fn read_only(id: &str) -> Failure {
+  ("Snippet is read-only", format!("The snippet '{id}' is shared with every server and synced from the snippets directory, change it there instead"))
 }
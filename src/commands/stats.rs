@@ -0,0 +1,97 @@
+use serenity::builder::CreateEmbed;
+use serenity::model::prelude::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::Timestamp;
+use serenity::prelude::Context;
+use std::collections::HashMap;
+use crate::storage::StorageContainer;
+use crate::structures::{State, UsageSource};
+
+use super::{arg_opt, list_ids, respond_embed, respond_err};
+
+const DEFAULT_DAYS: i64 = 30;
+const DEFAULT_LIMIT: usize = 10;
+
+pub(super) async fn snippet_stats(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  let days = match arg_opt(interaction, "days") {
+    Some(CommandDataOptionValue::Integer(days)) => days.clamp(1, 365),
+    _ => DEFAULT_DAYS
+  };
+
+  let limit = match arg_opt(interaction, "limit") {
+    Some(CommandDataOptionValue::Integer(limit)) => limit.clamp(1, 25) as usize,
+    _ => DEFAULT_LIMIT
+  };
+
+  let since = Timestamp::from_unix_timestamp(Timestamp::now().unix_timestamp() - days * 24 * 60 * 60)
+    .expect("Failed to create timestamp");
+
+  let (storage, visible) = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+    let visible: Vec<String> = state.snippets(interaction.guild_id).into_iter()
+      .map(|s| s.id.clone())
+      .collect();
+
+    (data.get::<StorageContainer>().expect("Failed to get storage").clone(), visible)
+  };
+
+  let result = tokio::task::spawn_blocking(move || storage.count_usage(Some(since)))
+    .await
+    .expect("Failed to join storage task");
+
+  let counts = match result {
+    Ok(counts) => counts,
+    Err(e) => {
+      let content = &format!("Unable to read snippet usage: {e}");
+      return respond_err(ctx, interaction, "Failed to get snippet statistics", content).await
+    }
+  };
+
+  let mut by_snippet: HashMap<String, u64> = HashMap::new();
+  let mut by_source: HashMap<UsageSource, u64> = HashMap::new();
+
+  for count in counts.into_iter().filter(|c| c.guild == interaction.guild_id) {
+    *by_source.entry(count.source).or_default() += count.count;
+    *by_snippet.entry(count.snippet).or_default() += count.count;
+  }
+
+  let total: u64 = by_source.values().sum();
+  let sources = [
+    (UsageSource::SlashCommand, "slash commands"),
+    (UsageSource::TextCommand, "text commands"),
+    (UsageSource::Search, "search"),
+    (UsageSource::Reply, "replies")
+  ].iter()
+    .map(|(source, name)| format!("{} from {name}", by_source.get(source).copied().unwrap_or_default()))
+    .collect::<Vec<String>>()
+    .join(", ");
+
+  let mut top: Vec<(&String, &u64)> = by_snippet.iter().collect();
+  top.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+  let top = top.iter()
+    .take(limit)
+    .enumerate()
+    .map(|(i, (id, count))| format!("**{}.** `{id}`: {count} uses", i + 1))
+    .collect::<Vec<String>>();
+
+  let unused: Vec<&str> = visible.iter()
+    .filter(|id| !by_snippet.contains_key(*id))
+    .map(String::as_str)
+    .collect();
+
+  let mut embed = CreateEmbed::default();
+  embed.title(format!("Snippet usage in the last {days} days"))
+    .description(format!("{total} uses: {sources}"))
+    .colour(super::ACCENT_COLOUR);
+
+  if !top.is_empty() {
+    embed.field(format!("Top {}", top.len()), top.join("\n").chars().take(1024).collect::<String>(), false);
+  }
+
+  if !unused.is_empty() {
+    embed.field(format!("Never used ({})", unused.len()), list_ids(&unused), false);
+  }
+
+  respond_embed(ctx, interaction, &embed, false).await
+}
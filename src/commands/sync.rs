@@ -0,0 +1,96 @@
+use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::UserId;
+use serenity::model::Timestamp;
+use serenity::prelude::Context;
+use std::path::Path;
+use crate::storage::{self, directory, Change, PersistenceContainer, StateError, Storage};
+use crate::structures::{AuditEntry, State};
+
+use super::{audit, is_owner, respond_embed, respond_err};
+use super::import_export::ImportPlan;
+
+/// Replaces the global snippets with the ones in the snippets directory and
+/// shows what changed.
+pub(super) async fn sync(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+  if !is_owner(ctx, interaction).await {
+    let content = "Only the bot owner may sync snippets";
+    return respond_err(ctx, interaction, "Insufficient permissions", content).await
+  }
+
+  let dir = match storage::get_snippets_dir() {
+    Some(dir) => dir,
+    None => {
+      let content = "No snippets directory is configured, set `TABLETBOT_SNIPPETS` to use one";
+      return respond_err(ctx, interaction, "Failed to sync snippets", content).await
+    }
+  };
+
+  let incoming = match directory::read_snippets(&dir) {
+    Ok(snippets) => snippets,
+    Err(e) => {
+      let content = &format!("Unable to read the snippets directory, nothing was changed: {e}");
+      return respond_err(ctx, interaction, "Failed to sync snippets", content).await
+    }
+  };
+
+  let (plan, records) = {
+    let mut data = ctx.data.write().await;
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+    let state = data.get_mut::<State>().expect("Failed to get state");
+
+    println!("Syncing {} snippets from '{}'", incoming.len(), dir.display());
+
+    let plan = ImportPlan::new(state.scope(None), incoming, true);
+    let records = plan.apply(state.scope_mut(None), interaction.user.id);
+
+    for (id, _, _) in &records {
+      persistence.mark(Change::Snippet { scope: None, id: id.clone() });
+    }
+
+    (plan, records)
+  };
+
+  for (id, before, after) in records {
+    audit(ctx, interaction, Some(&id), before, after).await;
+  }
+
+  respond_embed(ctx, interaction, &plan.embed("Snippets synced from directory", super::OK_COLOUR), false).await
+}
+
+/// Replaces the global snippets in `state` with the ones in `dir` when the
+/// bot starts, saving and auditing the changes as made by `author`.
+pub fn sync_on_start(storage: &dyn Storage, state: &mut State, dir: &Path, author: UserId) -> Result<(), StateError> {
+  let plan = ImportPlan::new(state.scope(None), directory::read_snippets(dir)?, true);
+
+  for id in plan.skipped() {
+    println!("Skipped snippet '{id}' from '{}', it is invalid or conflicts with another", dir.display());
+  }
+
+  if plan.is_empty() {
+    return Ok(())
+  }
+
+  let records = plan.apply(state.scope_mut(None), author);
+  storage.save(state)?;
+
+  println!("Synced {} changed snippets from '{}'", records.len(), dir.display());
+
+  for (id, before, after) in records {
+    let entry = AuditEntry {
+      timestamp: Timestamp::now(),
+      actor: author,
+      guild: None,
+      command: "sync".to_string(),
+      snippet: Some(id),
+      before,
+      after,
+      details: None
+    };
+
+    if let Err(e) = storage.append_audit(&entry) {
+      println!("Failed to record audit entry for '{}': {e}", entry.command);
+    }
+  }
+
+  Ok(())
+}
@@ -45,13 +45,13 @@ async fn http_get_body_text(url: &String) -> Option<String> {
         Ok(content) => Some(content),
         Err(e) => {
           println!("Failed to get text: {}", e);
-          return None
+          None
         }
       }
     },
     Err(e) => {
       println!("Failed to get response: {}", e);
-      return None
+      None
     }
   }
 }
@@ -76,7 +76,7 @@ struct FileReference<'a> {
 }
 
 impl FileReference<'_> {
-  pub fn try_from_str(text: &str) -> Option<Vec<FileReference>> {
+  pub fn try_from_str(text: &str) -> Option<Vec<FileReference<'_>>> {
     let r = Regex::new(r"https://github.com/(.+?)/(.+?)/blob/(.+?)/(.+?)#L([0-9]+)(?:-L([0-9]+))?")
       .expect("Expected url regex");
 
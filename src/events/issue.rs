@@ -7,8 +7,8 @@ use serenity::prelude::Context;
 use serenity::utils::Colour;
 use crate::structures::Embeddable;
 
-const REPO_OWNER: &str = "OpenTabletDriver";
-const REPO_NAME: &str = "OpenTabletDriver";
+pub(crate) const REPO_OWNER: &str = "OpenTabletDriver";
+pub(crate) const REPO_NAME: &str = "OpenTabletDriver";
 
 const OPEN_COLOUR: Colour = Colour(0x238636);
 const RESOLVED_COLOUR: Colour = Colour(0x8957e5);
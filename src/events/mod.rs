@@ -3,10 +3,11 @@ use serenity::prelude::Context;
 
 pub mod issue;
 pub mod code;
+pub mod snippet;
 
 pub async fn message(ctx: &Context, msg: &Message) {
   if !msg.author.bot {
-    issue::message(&ctx, &msg).await;
-    code::message(&ctx, &msg).await;
+    issue::message(ctx, msg).await;
+    code::message(ctx, msg).await;
   }
 }
@@ -0,0 +1,101 @@
+use serenity::framework::standard::macros::hook;
+use serenity::model::prelude::Message;
+use serenity::prelude::Context;
+use crate::commands::{self, ERROR_COLOUR};
+use crate::structures::{Embeddable, State, Usage, UsageSource};
+use crate::template::{self, Variables};
+
+/// Posts the snippet named by an otherwise unknown `!<id>` or mention
+/// command, as a reply to the message the command replied to if there is one.
+///
+/// Anything after the id is used as the snippet's arguments.
+#[hook]
+pub async fn snippet_command(ctx: &Context, msg: &Message, name: &str) {
+  let snippet = {
+    let data = ctx.data.read().await;
+    let state = data.get::<State>().expect("Failed to get state");
+    state.get_snippet(msg.guild_id, name).cloned()
+  };
+
+  let snippet = match snippet {
+    Some(snippet) => snippet,
+    None => return
+  };
+
+  // Text commands have no user locale, so the server's language is used
+  let locale = msg.guild_field(ctx, |g| g.preferred_locale.clone()).unwrap_or_default();
+
+  let target = msg.referenced_message.as_ref().map_or(msg.author.id, |m| m.author.id);
+  let platform = commands::user_platform(ctx, msg.guild_id, target).await;
+
+  let args = template::parse_args(command_args(&msg.content, name));
+  let variables = Variables::new(msg.author.id, msg.channel_id, args);
+
+  let (result, shown) = match commands::render_for_platform(ctx, msg.id.0, &snippet, &locale, platform, variables).await {
+    Ok((snippet, components)) => {
+      let reply_to = msg.referenced_message.as_deref();
+
+      let result = msg.channel_id.send_message(ctx, |m| {
+        if let Some(reply_to) = reply_to {
+          m.reference_message(reply_to);
+        }
+
+        m.set_embed(snippet.embed()).set_components(components)
+      }).await;
+
+      (result, true)
+    },
+    Err(e) => {
+      let result = msg.channel_id.send_message(ctx, |m| m
+        .reference_message(msg)
+        .embed(|embed| embed
+          .title("Failed to show snippet")
+          .description(e.to_string())
+          .colour(ERROR_COLOUR)
+        )
+      ).await;
+
+      (result, false)
+    }
+  };
+
+  match result {
+    Ok(_) if shown => {
+      let usage = Usage::new(msg.author.id, msg.guild_id, msg.channel_id, snippet.id, UsageSource::TextCommand);
+      commands::record_usage(ctx, usage).await
+    },
+    Ok(_) => (),
+    Err(e) => println!("Failed to post snippet '{}' for text command: {e}", snippet.id)
+  }
+}
+
+/// Returns what follows the `!` prefix or bot mention and the command `name`
+/// in `content`, which are the command's arguments.
+fn command_args<'a>(content: &'a str, name: &str) -> &'a str {
+  let content = content.trim_start();
+
+  let command = match content.strip_prefix("<@") {
+    Some(mention) => mention.split_once('>').map_or("", |(_, command)| command),
+    None => content.strip_prefix('!').unwrap_or(content)
+  };
+
+  command.trim_start().strip_prefix(name).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn args_after_prefix() {
+    assert_eq!(command_args("!logs linux \"two words\"", "logs"), " linux \"two words\"");
+    assert_eq!(command_args("!logs", "logs"), "");
+  }
+
+  #[test]
+  fn args_after_mention() {
+    // The id appears inside the mention, which must not be taken for the command
+    assert_eq!(command_args("<@1234> 12 34", "12"), " 34");
+    assert_eq!(command_args("<@!1234>   faq linux", "faq"), " linux");
+  }
+}
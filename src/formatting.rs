@@ -1,6 +1,6 @@
 pub fn trim_indent(lines: &[&str]) -> String {
   let base_indent = get_base_indent(lines);
-  let prefix = String::from_iter(std::iter::repeat(' ').take(base_indent));
+  let prefix = " ".repeat(base_indent);
 
   let trimmed_lines: Vec<&str> = lines.iter()
     .map(move |line| line.strip_prefix(&prefix).unwrap_or(line))
@@ -29,3 +29,95 @@ fn get_indent(line: &str) -> usize {
 
   0
 }
+
+/// The most changed lines on either side that are diffed line by line, as
+/// that takes time and memory proportional to the product of both. Beyond it
+/// the changed lines are shown as removed and added as a whole.
+const MAX_DIFF_LINES: usize = 300;
+
+/// A line-by-line diff from `before` to `after`, with removed lines prefixed
+/// by `-`, added lines by `+` and unchanged lines by a space.
+pub fn diff(before: &str, after: &str) -> String {
+  let old: Vec<&str> = before.lines().collect();
+  let new: Vec<&str> = after.lines().collect();
+
+  // Lines shared at the start and end are unchanged, only the rest is diffed
+  let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+  let suffix = old[prefix..].iter().rev()
+    .zip(new[prefix..].iter().rev())
+    .take_while(|(a, b)| a == b)
+    .count();
+
+  let old_changed = &old[prefix..old.len() - suffix];
+  let new_changed = &new[prefix..new.len() - suffix];
+
+  let mut lines: Vec<String> = old[..prefix].iter().map(|l| format!("  {l}")).collect();
+
+  if old_changed.len() > MAX_DIFF_LINES || new_changed.len() > MAX_DIFF_LINES {
+    lines.extend(old_changed.iter().map(|l| format!("- {l}")));
+    lines.extend(new_changed.iter().map(|l| format!("+ {l}")));
+  } else {
+    lines.extend(diff_lines(old_changed, new_changed));
+  }
+
+  lines.extend(old[old.len() - suffix..].iter().map(|l| format!("  {l}")));
+  lines.join("\n")
+}
+
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<String> {
+  // lengths[i][j] is the length of the longest common subsequence of
+  // old[i..] and new[j..]
+  let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+  for i in (0..old.len()).rev() {
+    for j in (0..new.len()).rev() {
+      lengths[i][j] = if old[i] == new[j] {
+        lengths[i + 1][j + 1] + 1
+      } else {
+        lengths[i + 1][j].max(lengths[i][j + 1])
+      };
+    }
+  }
+
+  let mut lines = Vec::new();
+  let (mut i, mut j) = (0, 0);
+
+  while i < old.len() || j < new.len() {
+    if i < old.len() && j < new.len() && old[i] == new[j] {
+      lines.push(format!("  {}", old[i]));
+      i += 1;
+      j += 1;
+    } else if j < new.len() && (i == old.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+      lines.push(format!("+ {}", new[j]));
+      j += 1;
+    } else {
+      lines.push(format!("- {}", old[i]));
+      i += 1;
+    }
+  }
+
+  lines
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn diffs_changed_lines() {
+    assert_eq!(diff("a\nb\nc", "a\nx\nc"), "  a\n+ x\n- b\n  c");
+    assert_eq!(diff("a", "a\nb"), "  a\n+ b");
+  }
+
+  #[test]
+  fn replaces_long_changes_as_a_whole() {
+    let lines = (0..=MAX_DIFF_LINES).map(|i| format!("{i}")).collect::<Vec<String>>().join("\n");
+    let before = format!("same\nold\n{lines}\nold\nsame");
+    let after = format!("same\nnew\n{lines}\nnew\nsame");
+
+    let diff = diff(&before, &after);
+    let diff: Vec<&str> = diff.lines().collect();
+    assert_eq!(&diff[..3], ["  same", "- old", "- 0"]);
+    assert_eq!(diff.iter().filter(|l| l.starts_with('+')).count(), MAX_DIFF_LINES + 3);
+    assert_eq!(diff.last(), Some(&"  same"));
+  }
+}
@@ -2,20 +2,32 @@ pub(crate) mod structures;
 pub(crate) mod commands;
 pub(crate) mod events;
 pub(crate) mod formatting;
+pub(crate) mod search;
+pub(crate) mod storage;
+pub(crate) mod template;
+pub(crate) mod watcher;
 
 use octocrab::Octocrab;
 use serenity::async_trait;
 use serenity::framework::StandardFramework;
 use serenity::http::Http;
 use serenity::model::application::interaction::*;
-use serenity::model::prelude::{Message, Ready, UserId};
+use serenity::model::prelude::{Guild, GuildId, Message, Ready, UserId};
 use serenity::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::storage::{Persistence, PersistenceContainer, StorageContainer, UsageLog, UsageLogContainer};
 use crate::structures::*;
 
 #[tokio::main]
 async fn main() {
+  if let Some("import") = env::args().nth(1).as_deref() {
+    return import();
+  }
+
   let discord_token = env::var("DISCORD_TOKEN").expect("Expected discord api token");
   let github_token = env::var("GITHUB_TOKEN").expect("Expected github api token");
 
@@ -34,17 +46,19 @@ async fn main() {
     .configure(|configuration| {
       configuration
         .on_mention(Some(UserId(*bot_id.as_u64())))
-        .owners(owners)
+        .owners(owners.clone())
         .prefix("!")
-    });
+    })
+    .unrecognised_command(events::snippet::snippet_command);
 
-  let intents = GatewayIntents::GUILD_MESSAGES
+  let intents = GatewayIntents::GUILDS
+    | GatewayIntents::GUILD_MESSAGES
     | GatewayIntents::DIRECT_MESSAGES
     | GatewayIntents::MESSAGE_CONTENT;
 
   let mut client = Client::builder(&discord_token, intents)
     .framework(framework)
-    .event_handler(Handler)
+    .event_handler(Handler { watching: AtomicBool::new(false) })
     .await
     .expect("Error creating client");
 
@@ -54,16 +68,55 @@ async fn main() {
   octocrab::initialise(octo_builder)
     .expect("Failed to build github client");
 
+  let storage = storage::from_env()
+    .unwrap_or_else(|e| panic!("Failed to open storage: {e}"));
+
+  let mut state = storage.load()
+    .unwrap_or_else(|e| panic!("Failed to load state from {} storage: {e}", storage.name()));
+
+  if let Some(dir) = storage::get_snippets_dir() {
+    if let Err(e) = commands::sync_on_start(storage.as_ref(), &mut state, &dir, UserId(*bot_id.as_u64())) {
+      println!("Failed to sync snippets from '{}', keeping the stored snippets: {e}", dir.display());
+    }
+  }
+
+  let mut usage_counts: HashMap<Option<GuildId>, HashMap<String, u64>> = HashMap::new();
+  match storage.count_usage(None) {
+    Ok(counts) => for count in counts {
+      *usage_counts.entry(count.guild).or_default().entry(count.snippet).or_default() += count.count;
+    },
+    Err(e) => println!("Failed to count snippet usage, ranking without it: {e}")
+  }
+
+  let persistence = Arc::new(Persistence::new(storage.clone()));
+  persistence.spawn(client.data.clone());
+
+  let usage_log = Arc::new(UsageLog::new(storage.clone()));
+  usage_log.spawn(client.data.clone());
+
   {
     let mut data = client.data.write().await;
-    data.insert::<State>(State::read());
+    data.insert::<State>(state);
+    data.insert::<StorageContainer>(storage);
+    data.insert::<PersistenceContainer>(persistence.clone());
+    data.insert::<UsageLogContainer>(usage_log.clone());
     data.insert::<ShardManagerContainer>(client.shard_manager.clone());
+    data.insert::<Owners>(owners);
+    data.insert::<UsageCounts>(usage_counts);
+    data.insert::<commands::PendingModals>(HashMap::new());
+    data.insert::<commands::ListFilters>(HashMap::new());
+    data.insert::<commands::ShownTabs>(HashMap::new());
   }
 
   let shard_manager = client.shard_manager.clone();
+  let data = client.data.clone();
   tokio::spawn(async move {
     tokio::signal::ctrl_c().await.expect("Could not register ctrl+c handler");
 
+    println!("Saving pending state changes");
+    persistence.flush(&data).await;
+    usage_log.flush(&data).await;
+
     println!("Disconnecting");
     shard_manager.lock().await.shutdown_all().await;
   });
@@ -73,7 +126,24 @@ async fn main() {
   }
 }
 
-struct Handler;
+/// Imports a JSON state file (`state.json` in the data root by default) into
+/// the SQLite database, run as `tabletbot import [path]`.
+fn import() {
+  let path = env::args().nth(2)
+    .map(PathBuf::from)
+    .unwrap_or_else(storage::get_state_path);
+
+  println!("Importing '{}' into '{}'", path.display(), storage::get_database_path().display());
+
+  match storage::import_json(&path) {
+    Ok(count) => println!("Imported {count} snippets"),
+    Err(e) => panic!("Failed to import state: {e}")
+  }
+}
+
+struct Handler {
+  watching: AtomicBool
+}
 
 #[async_trait]
 impl EventHandler for Handler {
@@ -81,6 +151,14 @@ impl EventHandler for Handler {
     println!("Connected to Discord API as bot user '{}#{:04}'", ready.user.name, ready.user.discriminator);
 
     commands::register(&ctx).await;
+
+    if !self.watching.swap(true, Ordering::Relaxed) {
+      tokio::spawn(watcher::watch(ctx));
+    }
+  }
+
+  async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: bool) {
+    commands::register_guild(&ctx, guild.id).await;
   }
 
   async fn message(&self, ctx: Context, msg: Message) {
@@ -99,9 +177,21 @@ impl EventHandler for Handler {
   }
 
   async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-    if let Interaction::ApplicationCommand(command) = interaction {
-      println!("Received command interaction '{}'", command.data.name);
-      commands::interact(&ctx, &command).await;
+    match interaction {
+      Interaction::ApplicationCommand(command) => {
+        println!("Received command interaction '{}'", command.data.name);
+        commands::interact(&ctx, &command).await;
+      },
+      Interaction::Autocomplete(autocomplete) => commands::autocomplete(&ctx, &autocomplete).await,
+      Interaction::MessageComponent(component) => {
+        println!("Received component interaction '{}'", component.data.custom_id);
+        commands::component(&ctx, &component).await;
+      },
+      Interaction::ModalSubmit(modal) => {
+        println!("Received modal submit interaction '{}'", modal.data.custom_id);
+        commands::modal(&ctx, &modal).await;
+      },
+      _ => ()
     }
   }
 }
@@ -0,0 +1,144 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use crate::structures::Snippet;
+
+/// Orders `snippets` by how well they match `query`, dropping the ones that
+/// do not match at all. Ids and aliases rank above titles, which rank above
+/// content.
+///
+/// Snippets that match equally well are ordered by how often they were used,
+/// going by `uses`, and an empty query matches every snippet.
+pub fn rank<'a>(snippets: Vec<&'a Snippet>, query: &str, uses: Option<&HashMap<String, u64>>) -> Vec<&'a Snippet> {
+  let query = query.trim().to_lowercase();
+
+  let mut ranked: Vec<(u32, &Snippet)> = snippets.into_iter()
+    .filter_map(|s| if query.is_empty() {
+      Some((0, s))
+    } else {
+      score(s, &query).map(|score| (score, s))
+    })
+    .collect();
+
+  ranked.sort_by_key(|(score, s)| (Reverse(*score), Reverse(use_count(uses, s))));
+  ranked.into_iter().map(|(_, s)| s).collect()
+}
+
+fn score(snippet: &Snippet, query: &str) -> Option<u32> {
+  let id = snippet.id.to_lowercase();
+  let title = snippet.title.to_lowercase();
+
+  let aliases: Vec<String> = snippet.aliases.iter().map(|a| a.to_lowercase()).collect();
+
+  if id == query {
+    Some(100)
+  } else if aliases.iter().any(|a| a == query) {
+    Some(90)
+  } else if id.starts_with(query) {
+    Some(80)
+  } else if aliases.iter().any(|a| a.starts_with(query)) {
+    Some(70)
+  } else if id.contains(query) {
+    Some(60)
+  } else if title.starts_with(query) {
+    Some(50)
+  } else if title.contains(query) {
+    Some(40)
+  } else if query.split_whitespace().all(|word| title.contains(word)) {
+    Some(30)
+  } else if snippet.content.to_lowercase().contains(query) {
+    Some(10)
+  } else {
+    None
+  }
+}
+
+/// The lowest similarity between a query word and a word in a snippet that
+/// still counts as a match, so that small typos are tolerated.
+const FUZZY_THRESHOLD: f64 = 0.8;
+
+/// Orders `snippets` by their typo-tolerant similarity to `query`, dropping
+/// the ones that do not match every query word.
+///
+/// Each query word is compared against the words of the id and aliases, the
+/// title, category and tags, and the content, in that order of importance,
+/// and the snippet scores the average of its best match for every word. Ties
+/// are broken by `uses`.
+pub fn fuzzy<'a>(snippets: Vec<&'a Snippet>, query: &str, uses: Option<&HashMap<String, u64>>) -> Vec<&'a Snippet> {
+  let query = words(query);
+
+  if query.is_empty() {
+    return snippets
+  }
+
+  let mut ranked: Vec<(f64, &Snippet)> = snippets.into_iter()
+    .filter_map(|s| {
+      let labels = std::iter::once(s.title.as_str())
+        .chain(s.category.as_deref())
+        .chain(s.tags.iter().map(String::as_str));
+
+      let fields = [(s.names().flat_map(words).collect(), 1.0), (labels.flat_map(words).collect(), 0.9), (words(&s.content), 0.6)];
+
+      let matches: Vec<f64> = query.iter()
+        .map(|q| fields.iter()
+          .map(|(words, weight)| weight * best_similarity(q, words))
+          .fold(0.0, f64::max)
+        )
+        .collect();
+
+      let score = matches.iter().sum::<f64>() / matches.len() as f64;
+      matches.iter().all(|m| *m > 0.0).then_some((score, s))
+    })
+    .collect();
+
+  ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| use_count(uses, b.1).cmp(&use_count(uses, a.1))));
+  ranked.into_iter().map(|(_, s)| s).collect()
+}
+
+/// Orders every snippet by how many words of `text`, such as a question,
+/// resemble a word of its id, aliases, title, category or tags, so that the
+/// snippets most likely to answer it come first. Ties are broken by `uses`.
+pub fn related<'a>(snippets: Vec<&'a Snippet>, text: &str, uses: Option<&HashMap<String, u64>>) -> Vec<&'a Snippet> {
+  let text: Vec<String> = words(text).into_iter()
+    .filter(|w| w.chars().count() > 2)
+    .collect();
+
+  let mut ranked: Vec<(usize, &Snippet)> = snippets.into_iter()
+    .map(|s| {
+      let labels: Vec<String> = s.names()
+        .chain(std::iter::once(s.title.as_str()))
+        .chain(s.category.as_deref())
+        .chain(s.tags.iter().map(String::as_str))
+        .flat_map(words)
+        .collect();
+
+      (text.iter().filter(|w| best_similarity(w, &labels) > 0.0).count(), s)
+    })
+    .collect();
+
+  ranked.sort_by_key(|(score, s)| (Reverse(*score), Reverse(use_count(uses, s))));
+  ranked.into_iter().map(|(_, s)| s).collect()
+}
+
+fn use_count(uses: Option<&HashMap<String, u64>>, snippet: &Snippet) -> u64 {
+  uses.and_then(|u| u.get(&snippet.id)).copied().unwrap_or_default()
+}
+
+fn best_similarity(query: &str, words: &[String]) -> f64 {
+  words.iter()
+    .map(|word| {
+      if word.starts_with(query) {
+        1.0
+      } else {
+        strsim::jaro_winkler(query, word)
+      }
+    })
+    .filter(|similarity| *similarity >= FUZZY_THRESHOLD)
+    .fold(0.0, f64::max)
+}
+
+fn words(text: &str) -> Vec<String> {
+  text.split(|c: char| !c.is_alphanumeric())
+    .filter(|w| !w.is_empty())
+    .map(str::to_lowercase)
+    .collect()
+}
@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::Path;
+use crate::structures::Snippet;
+use super::StateError;
+
+/// Reads every `.md` file in `dir` as a snippet, in file name order.
+///
+/// Each file starts with front matter between `---` lines, holding `key:
+/// value` pairs. `title` is required, while `id` defaults to the file name.
+/// `category` is optional and `tags` and `aliases` are lists written either
+/// as `[a, b]` or `a, b`. Everything after the front matter is the content.
+///
+/// ```markdown
+/// ---
+/// id: tablet-not-detected
+/// title: My tablet is not detected
+/// tags: [drivers, udev]
+/// aliases: nodetect
+/// ---
+/// Make sure the udev rules are installed...
+/// ```
+pub fn read_snippets(dir: &Path) -> Result<Vec<Snippet>, StateError> {
+  let mut paths = Vec::new();
+
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+
+    if path.is_file() && path.extension().is_some_and(|e| e == "md") {
+      paths.push(path);
+    }
+  }
+
+  paths.sort();
+
+  paths.iter()
+    .map(|path| {
+      let text = fs::read_to_string(path)?;
+      let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+
+      parse_snippet(&stem, &text)
+        .map_err(|e| StateError::FrontMatter(path.display().to_string(), e))
+    })
+    .collect()
+}
+
+fn parse_snippet(stem: &str, text: &str) -> Result<Snippet, String> {
+  let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+
+  let rest = text.strip_prefix("---\n")
+    .ok_or("The file must start with '---'")?;
+
+  let (front_matter, content) = match rest.split_once("\n---\n") {
+    Some(parts) => parts,
+    None => rest.strip_suffix("\n---").map(|f| (f, "")).ok_or("The front matter is not closed with '---'")?
+  };
+
+  let mut snippet = Snippet::new(stem.to_string(), String::new(), content.trim().to_string());
+
+  for line in front_matter.lines().filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#')) {
+    let (key, value) = line.split_once(':')
+      .ok_or_else(|| format!("'{line}' is not a 'key: value' pair"))?;
+
+    let value = unquote(value.trim());
+
+    match key.trim() {
+      "id" => snippet.id = value.to_string(),
+      "title" => snippet.title = value.to_string(),
+      "category" => snippet.category = (!value.is_empty()).then(|| value.to_string()),
+      "tags" => snippet.tags = parse_list(value),
+      "aliases" => snippet.aliases = parse_list(value),
+      other => return Err(format!("Unknown key '{other}', expected id, title, category, tags or aliases"))
+    }
+  }
+
+  if snippet.id.is_empty() {
+    return Err("The id is empty".to_string())
+  }
+
+  if snippet.title.is_empty() {
+    return Err("The title is missing".to_string())
+  }
+
+  Ok(snippet)
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+  let value = value.strip_prefix('[')
+    .and_then(|v| v.strip_suffix(']'))
+    .unwrap_or(value);
+
+  value.split(',')
+    .map(|item| unquote(item.trim()).to_string())
+    .filter(|item| !item.is_empty())
+    .collect()
+}
+
+fn unquote(value: &str) -> &str {
+  ['"', '\''].iter()
+    .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+    .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_front_matter() {
+    let text = "---\nid: nodetect\ntitle: My tablet is not detected\ncategory: Linux\n# A comment\n\ntags: [drivers, udev]\naliases: detect, missing\n---\n\nInstall the udev rules.\n";
+    let snippet = parse_snippet("tablet-not-detected", text).expect("Failed to parse");
+
+    assert_eq!(snippet.id, "nodetect");
+    assert_eq!(snippet.title, "My tablet is not detected");
+    assert_eq!(snippet.category.as_deref(), Some("Linux"));
+    assert_eq!(snippet.tags, ["drivers", "udev"]);
+    assert_eq!(snippet.aliases, ["detect", "missing"]);
+    assert_eq!(snippet.content, "Install the udev rules.");
+  }
+
+  #[test]
+  fn accepts_bom_and_crlf() {
+    let text = "\u{feff}---\r\ntitle: Logs\r\n---\r\nSend your logs.\r\n";
+    let snippet = parse_snippet("logs", text).expect("Failed to parse");
+
+    assert_eq!(snippet.title, "Logs");
+    assert_eq!(snippet.content, "Send your logs.");
+  }
+
+  #[test]
+  fn defaults_id_to_file_stem() {
+    let snippet = parse_snippet("logs", "---\ntitle: Logs\n---\n").expect("Failed to parse");
+    assert_eq!(snippet.id, "logs");
+    assert_eq!(snippet.content, "");
+
+    let snippet = parse_snippet("logs", "---\ntitle: Logs\n---").expect("Failed to parse");
+    assert_eq!(snippet.id, "logs");
+
+    assert!(parse_snippet("logs", "---\nid: \"\"\ntitle: Logs\n---\n").is_err());
+  }
+
+  #[test]
+  fn requires_closed_front_matter() {
+    assert!(parse_snippet("logs", "title: Logs\n---\nSend your logs.").is_err());
+    assert!(parse_snippet("logs", "---\ntitle: Logs\nSend your logs.").is_err());
+  }
+
+  #[test]
+  fn rejects_invalid_lines() {
+    match parse_snippet("logs", "---\ntitle: Logs\ncolour: red\n---\n") {
+      Err(e) => assert!(e.contains("'colour'")),
+      Ok(_) => panic!("Accepted an unknown key")
+    }
+
+    assert!(parse_snippet("logs", "---\ntitle: Logs\njust text\n---\n").is_err());
+    assert!(parse_snippet("logs", "---\nid: logs\n---\n").is_err());
+  }
+
+  #[test]
+  fn parses_lists() {
+    assert_eq!(parse_list("[a, b]"), ["a", "b"]);
+    assert_eq!(parse_list("a, b"), ["a", "b"]);
+    assert_eq!(parse_list("['a', \"b\", ]"), ["a", "b"]);
+    assert!(parse_list("[]").is_empty());
+    assert!(parse_list("").is_empty());
+  }
+
+  #[test]
+  fn unquotes_values() {
+    assert_eq!(unquote("\"Logs: where to find them\""), "Logs: where to find them");
+    assert_eq!(unquote("'Logs'"), "Logs");
+    assert_eq!(unquote("\"Logs'"), "\"Logs'");
+    assert_eq!(unquote("\""), "\"");
+    assert_eq!(unquote("Logs"), "Logs");
+
+    let snippet = parse_snippet("logs", "---\ntitle: \"Logs: where to find them\"\n---\n").expect("Failed to parse");
+    assert_eq!(snippet.title, "Logs: where to find them");
+  }
+}
@@ -0,0 +1,318 @@
+use chrono::{NaiveDateTime, Utc};
+use serde_json::{from_reader, from_str, from_value, to_string, to_writer_pretty, Value};
+use serenity::model::prelude::GuildId;
+use serenity::model::Timestamp;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use crate::structures::{AuditEntry, State, Usage, UsageCount, UsageSource};
+use super::migrations::{self, STATE_VERSION};
+use super::{AuditFilter, StateError, Storage};
+
+pub const DEFAULT_BACKUP_COUNT: usize = 10;
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// Stores the state as a single JSON file, keeping rolling backups of the
+/// previous versions in `backup_dir`.
+pub struct JsonStorage {
+  path: PathBuf,
+  /// The audit log, written as one JSON entry per line.
+  audit_path: PathBuf,
+  /// The usage log, written as one JSON entry per line.
+  usage_path: PathBuf,
+  backup_dir: PathBuf,
+  backup_count: usize,
+  /// The modification time of the state file as of the last time this
+  /// storage read or wrote it, used to notice edits made by hand.
+  modified: Mutex<Option<SystemTime>>
+}
+
+impl JsonStorage {
+  pub fn new(path: PathBuf, audit_path: PathBuf, usage_path: PathBuf, backup_dir: PathBuf, backup_count: usize) -> JsonStorage {
+    Self { path, audit_path, usage_path, backup_dir, backup_count, modified: Mutex::new(None) }
+  }
+
+  fn get_modified(&self) -> Option<SystemTime> {
+    fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+  }
+
+  fn record_modified(&self) {
+    *self.modified.lock().expect("Failed to lock modification time") = self.get_modified();
+  }
+
+  pub fn read_from(path: &Path) -> Result<State, StateError> {
+    Self::read_versioned(path).map(|(state, _)| state)
+  }
+
+  /// Reads and migrates the state at `path`, also returning the version it
+  /// was stored as.
+  fn read_versioned(path: &Path) -> Result<(State, u64), StateError> {
+    let file = File::open(path)?;
+    let value: Value = from_reader(BufReader::new(file))?;
+    let (value, version) = migrations::migrate(value)?;
+
+    Ok((from_value(value)?, version))
+  }
+
+  fn quarantine(&self) -> Result<PathBuf, StateError> {
+    let timestamp = Utc::now().format(BACKUP_TIMESTAMP_FORMAT);
+    let quarantined = self.path.with_extension(format!("json.corrupt-{timestamp}"));
+    fs::rename(&self.path, &quarantined)?;
+
+    Ok(quarantined)
+  }
+
+  fn recover(&self) -> Option<(Backup, State)> {
+    let backups = match self.list_backups() {
+      Ok(backups) => backups,
+      Err(e) => {
+        println!("Failed to list backups: {e}");
+        return None
+      }
+    };
+
+    for backup in backups {
+      match Self::read_from(&backup.path) {
+        Ok(state) => return Some((backup, state)),
+        Err(e) => println!("Skipping unreadable backup '{}': {e}", backup.name)
+      }
+    }
+
+    None
+  }
+
+  /// Serializes the state into a temporary file next to the state file, backs
+  /// up the current file and then renames the temporary file over it, so that
+  /// a crash mid-write never leaves a truncated state behind.
+  fn write_atomic(&self, state: &State) -> Result<(), StateError> {
+    let tmp_path = self.path.with_extension("json.tmp");
+
+    {
+      let mut writer = BufWriter::new(File::create(&tmp_path)?);
+      to_writer_pretty(&mut writer, state)?;
+      writer.flush()?;
+      writer.get_ref().sync_all()?;
+    }
+
+    if self.path.exists() {
+      if let Err(e) = self.backup() {
+        println!("Failed to back up state '{}': {e}", self.path.display());
+      }
+    }
+
+    // The new modification time is recorded under the same lock as the
+    // rename, so that `reload_if_changed` never takes this write for an edit
+    let mut modified = self.modified.lock().expect("Failed to lock modification time");
+    fs::rename(&tmp_path, &self.path)?;
+    *modified = self.get_modified();
+
+    Ok(())
+  }
+
+  fn backup(&self) -> Result<(), StateError> {
+    fs::create_dir_all(&self.backup_dir)?;
+
+    let name = format!("state-{}.json", Utc::now().format(BACKUP_TIMESTAMP_FORMAT));
+    fs::copy(&self.path, self.backup_dir.join(name))?;
+
+    for backup in self.list_backups()?.iter().skip(self.backup_count) {
+      fs::remove_file(&backup.path)?;
+    }
+
+    Ok(())
+  }
+}
+
+impl Storage for JsonStorage {
+  fn name(&self) -> &'static str {
+    "json"
+  }
+
+  /// Reads the state file, recovering from the newest valid backup if it
+  /// cannot be parsed. The unparseable file is quarantined rather than
+  /// overwritten. A file from a newer build or one that cannot be read at all
+  /// is left alone and the error returned, so that it is never replaced.
+  fn load(&self) -> Result<State, StateError> {
+    let path_str = self.path.display();
+
+    if !self.path.exists() {
+      return Ok(State::default())
+    }
+
+    let e = match Self::read_versioned(&self.path) {
+      Ok((state, version)) if version < STATE_VERSION => {
+        println!("Upgraded state from version {version} to {STATE_VERSION}");
+        self.save(&state)?;
+        return Ok(state)
+      },
+      Ok((state, _)) => {
+        self.record_modified();
+        return Ok(state)
+      },
+      Err(e @ (StateError::Io(_) | StateError::UnsupportedVersion(_))) => return Err(e),
+      Err(e) => e
+    };
+
+    println!("Failed to read state from '{path_str}': {e}");
+
+    match self.quarantine() {
+      Ok(quarantined) => println!("Moved unreadable state to '{}'", quarantined.display()),
+      Err(e) => println!("Failed to quarantine unreadable state '{path_str}': {e}")
+    }
+
+    match self.recover() {
+      Some((backup, state)) => {
+        println!("Recovered {} snippets from backup '{}'", state.snippet_count(), backup.name);
+        self.save(&state)?;
+        Ok(state)
+      },
+      None => {
+        println!("No valid backup found, starting with an empty state");
+        Ok(State::default())
+      }
+    }
+  }
+
+  fn save(&self, state: &State) -> Result<(), StateError> {
+    self.write_atomic(state)?;
+    println!("Successfully saved state to '{}'", self.path.display());
+
+    Ok(())
+  }
+
+  fn reload_if_changed(&self) -> Option<Result<State, StateError>> {
+    let mut known = self.modified.lock().expect("Failed to lock modification time");
+    let modified = self.get_modified()?;
+
+    if *known == Some(modified) {
+      return None
+    }
+
+    // The modification time is only recorded once the file is valid, so that
+    // a broken edit is read again until it is fixed
+    let result = Self::read_from(&self.path).and_then(|state| match state.validate() {
+      Ok(_) => Ok(state),
+      Err(e) => Err(StateError::Invalid(e))
+    });
+
+    if result.is_ok() {
+      println!("State file '{}' was changed on disk", self.path.display());
+      *known = Some(modified);
+    }
+
+    Some(result)
+  }
+
+  fn append_audit(&self, entry: &AuditEntry) -> Result<(), StateError> {
+    append_line(&self.audit_path, &to_string(entry)?)
+  }
+
+  fn query_audit(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, StateError> {
+    if !self.audit_path.exists() {
+      return Ok(Vec::new())
+    }
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(File::open(&self.audit_path)?).lines() {
+      let line = line?;
+      if line.is_empty() {
+        continue
+      }
+
+      match from_str::<AuditEntry>(&line) {
+        Ok(entry) if filter.matches(&entry) => entries.push(entry),
+        Ok(_) => (),
+        Err(e) => println!("Skipping unreadable audit entry: {e}")
+      }
+    }
+
+    entries.reverse();
+    entries.truncate(filter.limit);
+    Ok(entries)
+  }
+
+  fn append_usage(&self, usage: &Usage) -> Result<(), StateError> {
+    append_line(&self.usage_path, &to_string(usage)?)
+  }
+
+  fn count_usage(&self, since: Option<Timestamp>) -> Result<Vec<UsageCount>, StateError> {
+    if !self.usage_path.exists() {
+      return Ok(Vec::new())
+    }
+
+    let mut counts: HashMap<(Option<GuildId>, String, UsageSource), u64> = HashMap::new();
+    for line in BufReader::new(File::open(&self.usage_path)?).lines() {
+      let line = line?;
+      if line.is_empty() {
+        continue
+      }
+
+      match from_str::<Usage>(&line) {
+        Ok(usage) if since.map_or(true, |since| usage.timestamp >= since) => {
+          *counts.entry((usage.guild, usage.snippet, usage.source)).or_default() += 1;
+        },
+        Ok(_) => (),
+        Err(e) => println!("Skipping unreadable usage entry: {e}")
+      }
+    }
+
+    Ok(counts.into_iter()
+      .map(|((guild, snippet, source), count)| UsageCount { guild, snippet, source, count })
+      .collect())
+  }
+
+  fn list_backups(&self) -> Result<Vec<Backup>, StateError> {
+    if !self.backup_dir.exists() {
+      return Ok(Vec::new())
+    }
+
+    let mut backups: Vec<Backup> = fs::read_dir(&self.backup_dir)?
+      .filter_map(|entry| entry.ok())
+      .filter_map(|entry| Backup::from_path(entry.path()))
+      .collect();
+
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created));
+    Ok(backups)
+  }
+
+  fn restore_backup(&self, name: &str) -> Result<State, StateError> {
+    let backup = self.list_backups()?
+      .into_iter()
+      .find(|b| b.name == name)
+      .ok_or_else(|| StateError::BackupNotFound(name.to_string()))?;
+
+    let state = Self::read_from(&backup.path)?;
+    self.write_atomic(&state)?;
+
+    Ok(state)
+  }
+}
+
+pub struct Backup {
+  pub name: String,
+  pub path: PathBuf,
+  pub created: NaiveDateTime
+}
+
+impl Backup {
+  fn from_path(path: PathBuf) -> Option<Backup> {
+    let name = path.file_name()?.to_str()?.to_string();
+    let timestamp = name.strip_prefix("state-")?.strip_suffix(".json")?;
+    let created = NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT).ok()?;
+
+    Some(Backup { name, path, created })
+  }
+}
+
+fn append_line(path: &Path, line: &str) -> Result<(), StateError> {
+  let mut file = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path)?;
+
+  file.write_all(format!("{line}\n").as_bytes())?;
+  Ok(())
+}
@@ -0,0 +1,179 @@
+use serde_json::{Map, Value};
+use super::StateError;
+
+/// The version written by this build. Bump this and append a step to
+/// `MIGRATIONS` whenever the persisted format changes shape.
+pub const STATE_VERSION: u64 = 3;
+
+type Migration = fn(Map<String, Value>) -> Result<Map<String, Value>, StateError>;
+
+/// `MIGRATIONS[n]` upgrades a state from version `n` to version `n + 1`.
+const MIGRATIONS: [Migration; STATE_VERSION as usize] = [
+  v0_to_v1,
+  v1_to_v2,
+  v2_to_v3
+];
+
+/// Upgrades a raw state document to `STATE_VERSION`, returning the upgraded
+/// document and the version it was stored as.
+pub fn migrate(value: Value) -> Result<(Value, u64), StateError> {
+  let mut state = match value {
+    Value::Object(map) => map,
+    _ => return Err(StateError::Migration("State is not a JSON object".to_string()))
+  };
+
+  let version = get_version(&state)?;
+
+  if version > STATE_VERSION {
+    return Err(StateError::UnsupportedVersion(version))
+  }
+
+  for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+    let to = from as u64 + 1;
+    println!("Migrating state from version {from} to {to}");
+
+    state = migration(state)?;
+    state.insert("version".to_string(), Value::from(to));
+  }
+
+  Ok((Value::Object(state), version))
+}
+
+fn get_version(state: &Map<String, Value>) -> Result<u64, StateError> {
+  match state.get("version") {
+    None => Ok(0),
+    Some(version) => version.as_u64()
+      .ok_or_else(|| StateError::Migration(format!("Invalid state version '{version}'")))
+  }
+}
+
+/// Version 0 is the unversioned format, which only differs by the missing
+/// `version` field.
+fn v0_to_v1(state: Map<String, Value>) -> Result<Map<String, Value>, StateError> {
+  if !state.contains_key("snippets") {
+    return Err(StateError::Migration("Missing 'snippets' field".to_string()))
+  }
+
+  Ok(state)
+}
+
+/// Version 2 partitions snippets by guild. Existing snippets were visible in
+/// every guild, so they become global snippets.
+fn v1_to_v2(mut state: Map<String, Value>) -> Result<Map<String, Value>, StateError> {
+  state.entry("guilds").or_insert_with(|| Value::Object(Map::new()));
+
+  Ok(state)
+}
+
+/// Version 3 numbers revisions, which were numbered by their position before
+/// and so changed once the oldest ones were dropped.
+fn v2_to_v3(mut state: Map<String, Value>) -> Result<Map<String, Value>, StateError> {
+  if let Some(snippets) = state.get_mut("snippets") {
+    number_revisions(snippets);
+  }
+
+  if let Some(guilds) = state.get_mut("guilds").and_then(Value::as_object_mut) {
+    for snippets in guilds.values_mut().filter_map(|guild| guild.get_mut("snippets")) {
+      number_revisions(snippets);
+    }
+  }
+
+  Ok(state)
+}
+
+fn number_revisions(snippets: &mut Value) {
+  let snippets = snippets.as_array_mut().into_iter().flatten();
+  let revisions = snippets.filter_map(|s| s.get_mut("revisions").and_then(Value::as_array_mut));
+
+  for revisions in revisions {
+    for (i, revision) in revisions.iter_mut().enumerate() {
+      if let Some(revision) = revision.as_object_mut() {
+        revision.entry("number").or_insert_with(|| Value::from(i as u64 + 1));
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::{from_str, from_value, json};
+  use crate::structures::State;
+  use super::super::read_fixture;
+  use super::*;
+
+  fn read_object(name: &str) -> Map<String, Value> {
+    match from_str(&read_fixture(name)).expect("Fixture is not valid JSON") {
+      Value::Object(map) => map,
+      _ => panic!("Fixture '{name}' is not a JSON object")
+    }
+  }
+
+  #[test]
+  fn v0_to_v1_keeps_snippets() {
+    let state = v0_to_v1(read_object("state-v0.json")).expect("Failed to migrate");
+
+    assert_eq!(state["snippets"].as_array().map(Vec::len), Some(2));
+    assert_eq!(state["snippets"][1]["id"], "logs");
+  }
+
+  #[test]
+  fn v0_to_v1_requires_snippets() {
+    assert!(matches!(v0_to_v1(Map::new()), Err(StateError::Migration(_))));
+  }
+
+  #[test]
+  fn v1_to_v2_adds_guilds() {
+    let state = v1_to_v2(read_object("state-v1.json")).expect("Failed to migrate");
+
+    assert_eq!(state["guilds"], json!({}));
+    assert_eq!(state["snippets"][0]["id"], "faq");
+  }
+
+  #[test]
+  fn v2_to_v3_numbers_revisions() {
+    let state = v2_to_v3(read_object("state-v2.json")).expect("Failed to migrate");
+
+    let numbers = |revisions: &Value| revisions.as_array()
+      .map(|r| r.iter().map(|r| r["number"].as_u64()).collect::<Vec<Option<u64>>>());
+
+    assert_eq!(numbers(&state["snippets"][0]["revisions"]), Some(vec![Some(1), Some(2)]));
+    assert_eq!(numbers(&state["guilds"]["1000"]["snippets"][0]["revisions"]), Some(vec![Some(1)]));
+    assert!(state["snippets"][1].get("revisions").is_none());
+  }
+
+  #[test]
+  fn migrates_v0_to_current() {
+    let (value, version) = migrate(Value::Object(read_object("state-v0.json"))).expect("Failed to migrate");
+    assert_eq!(version, 0);
+    assert_eq!(value["version"], STATE_VERSION);
+
+    let state: State = from_value(value).expect("Migrated state does not deserialize");
+    assert_eq!(state.snippets.len(), 2);
+    assert!(state.guilds.is_empty());
+  }
+
+  #[test]
+  fn migrates_v1_to_current() {
+    let (value, version) = migrate(Value::Object(read_object("state-v1.json"))).expect("Failed to migrate");
+    assert_eq!(version, 1);
+    assert_eq!(value["version"], STATE_VERSION);
+
+    let state: State = from_value(value).expect("Migrated state does not deserialize");
+    assert_eq!(state.snippets[0].title, "Frequently asked questions");
+  }
+
+  #[test]
+  fn migrates_v2_to_current() {
+    let (value, version) = migrate(Value::Object(read_object("state-v2.json"))).expect("Failed to migrate");
+    assert_eq!(version, 2);
+
+    let state: State = from_value(value).expect("Migrated state does not deserialize");
+    assert_eq!(state.snippets[0].revisions[1].number, 2);
+  }
+
+  #[test]
+  fn rejects_newer_versions() {
+    let result = migrate(json!({ "version": STATE_VERSION + 1, "snippets": [] }));
+    assert!(matches!(result, Err(StateError::UnsupportedVersion(v)) if v == STATE_VERSION + 1));
+  }
+}
@@ -0,0 +1,225 @@
+use serenity::prelude::TypeMapKey;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serenity::model::prelude::{GuildId, UserId};
+use serenity::model::Timestamp;
+use crate::structures::{AuditEntry, State, Usage, UsageCount};
+
+pub mod directory;
+pub mod json;
+pub mod migrations;
+pub mod persistence;
+pub mod sqlite;
+pub mod usage;
+
+pub use json::Backup;
+use json::JsonStorage;
+use migrations::STATE_VERSION;
+pub use persistence::{Change, Persistence, PersistenceContainer};
+use sqlite::SqliteStorage;
+pub use usage::{UsageLog, UsageLogContainer};
+
+/// Persists the bot's `State`.
+///
+/// Backends only have to be able to load and save the state as a whole,
+/// applying individual changes falls back to a full save unless a backend can
+/// do better.
+pub trait Storage: Send + Sync {
+  fn name(&self) -> &'static str;
+
+  fn load(&self) -> Result<State, StateError>;
+
+  fn save(&self, state: &State) -> Result<(), StateError>;
+
+  /// Persists `changes`, which have already been applied to `state`.
+  fn apply(&self, state: &State, _changes: &[Change]) -> Result<(), StateError> {
+    self.save(state)
+  }
+
+  /// Reads and validates the stored state again if it was changed by
+  /// something other than this storage, such as a maintainer editing it by
+  /// hand. Returns `None` if it is unchanged since the last successful read.
+  fn reload_if_changed(&self) -> Option<Result<State, StateError>> {
+    None
+  }
+
+  fn append_audit(&self, entry: &AuditEntry) -> Result<(), StateError>;
+
+  /// Returns the audit entries matching `filter`, newest first.
+  fn query_audit(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, StateError>;
+
+  fn append_usage(&self, usage: &Usage) -> Result<(), StateError>;
+
+  /// Counts how often each snippet was shown since `since`, or ever if
+  /// `None`, by guild, snippet and source.
+  fn count_usage(&self, since: Option<Timestamp>) -> Result<Vec<UsageCount>, StateError>;
+
+  /// Lists the available backups, newest first.
+  fn list_backups(&self) -> Result<Vec<Backup>, StateError> {
+    Err(StateError::Unsupported("backups"))
+  }
+
+  /// Replaces the stored state with the backup named `name`, returning the
+  /// restored state.
+  fn restore_backup(&self, _name: &str) -> Result<State, StateError> {
+    Err(StateError::Unsupported("backups"))
+  }
+}
+
+pub struct StorageContainer;
+
+impl TypeMapKey for StorageContainer {
+  type Value = Arc<dyn Storage>;
+}
+
+pub struct AuditFilter {
+  pub guild: Option<GuildId>,
+  pub actor: Option<UserId>,
+  pub snippet: Option<String>,
+  pub since: Option<Timestamp>,
+  pub until: Option<Timestamp>,
+  pub limit: usize
+}
+
+impl AuditFilter {
+  pub fn matches(&self, entry: &AuditEntry) -> bool {
+    entry.guild == self.guild
+      && self.actor.map_or(true, |actor| entry.actor == actor)
+      && self.snippet.as_ref().map_or(true, |id| entry.snippet.as_ref() == Some(id))
+      && self.since.map_or(true, |since| entry.timestamp >= since)
+      && self.until.map_or(true, |until| entry.timestamp < until)
+  }
+}
+
+/// Opens the storage backend selected by `TABLETBOT_STORAGE`, which is either
+/// `json` (the default) or `sqlite`.
+pub fn from_env() -> Result<Arc<dyn Storage>, StateError> {
+  match env::var("TABLETBOT_STORAGE").as_deref() {
+    Ok("json") | Err(_) => Ok(Arc::new(JsonStorage::new(get_state_path(), get_audit_path(), get_usage_path(), get_backup_dir(), get_backup_count()))),
+    Ok("sqlite") => Ok(Arc::new(SqliteStorage::open(&get_database_path())?)),
+    Ok(other) => Err(StateError::UnknownBackend(other.to_string()))
+  }
+}
+
+/// Imports the JSON state at `path` into the configured SQLite database,
+/// replacing its contents.
+pub fn import_json(path: &Path) -> Result<usize, StateError> {
+  let state = JsonStorage::read_from(path)?;
+  let database = SqliteStorage::open(&get_database_path())?;
+  database.save(&state)?;
+
+  Ok(state.snippet_count())
+}
+
+pub fn get_data_root() -> PathBuf {
+  match env::var("TABLETBOT_DATA") {
+    Ok(path) => PathBuf::from(path),
+    Err(_) => env::current_dir().expect("Failed to get working directory")
+  }
+}
+
+pub fn get_state_path() -> PathBuf {
+  match env::var("TABLETBOT_STATE") {
+    Ok(path) => PathBuf::from(path),
+    Err(_) => get_data_root().join("state.json")
+  }
+}
+
+pub fn get_audit_path() -> PathBuf {
+  get_data_root().join("audit.jsonl")
+}
+
+pub fn get_usage_path() -> PathBuf {
+  get_data_root().join("usage.jsonl")
+}
+
+pub fn get_database_path() -> PathBuf {
+  match env::var("TABLETBOT_DATABASE") {
+    Ok(path) => PathBuf::from(path),
+    Err(_) => get_data_root().join("state.db")
+  }
+}
+
+pub fn get_backup_dir() -> PathBuf {
+  get_data_root().join("backups")
+}
+
+pub fn get_backup_count() -> usize {
+  env::var("TABLETBOT_BACKUPS").ok()
+    .and_then(|count| count.parse().ok())
+    .unwrap_or(json::DEFAULT_BACKUP_COUNT)
+}
+
+/// The directory of Markdown snippets set by `TABLETBOT_SNIPPETS`, which
+/// global snippets are synced from.
+pub fn get_snippets_dir() -> Option<PathBuf> {
+  env::var("TABLETBOT_SNIPPETS").ok().map(PathBuf::from)
+}
+
+/// Whether global snippets may only be changed through the snippets
+/// directory, set by `TABLETBOT_SNIPPETS_READONLY`.
+pub fn is_read_only() -> bool {
+  get_snippets_dir().is_some() && env::var("TABLETBOT_SNIPPETS_READONLY").is_ok_and(|v| v == "1" || v == "true")
+}
+
+#[derive(Debug)]
+pub enum StateError {
+  Io(io::Error),
+  Json(serde_json::Error),
+  Sqlite(rusqlite::Error),
+  BackupNotFound(String),
+  Migration(String),
+  Invalid(String),
+  FrontMatter(String, String),
+  UnsupportedVersion(u64),
+  UnknownBackend(String),
+  Unsupported(&'static str)
+}
+
+impl fmt::Display for StateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      StateError::Io(e) => write!(f, "I/O error: {e}"),
+      StateError::Json(e) => write!(f, "Invalid state JSON: {e}"),
+      StateError::Sqlite(e) => write!(f, "SQLite error: {e}"),
+      StateError::BackupNotFound(name) => write!(f, "No backup named '{name}'"),
+      StateError::Migration(e) => write!(f, "Failed to migrate state: {e}"),
+      StateError::Invalid(e) => write!(f, "Invalid state: {e}"),
+      StateError::FrontMatter(path, e) => write!(f, "Invalid snippet file '{path}': {e}"),
+      StateError::UnsupportedVersion(v) => write!(f, "State version {v} is newer than the supported version {STATE_VERSION}"),
+      StateError::UnknownBackend(name) => write!(f, "Unknown storage backend '{name}', expected 'json' or 'sqlite'"),
+      StateError::Unsupported(what) => write!(f, "This storage backend does not support {what}")
+    }
+  }
+}
+
+impl Error for StateError {}
+
+impl From<io::Error> for StateError {
+  fn from(e: io::Error) -> Self {
+    StateError::Io(e)
+  }
+}
+
+impl From<serde_json::Error> for StateError {
+  fn from(e: serde_json::Error) -> Self {
+    StateError::Json(e)
+  }
+}
+
+impl From<rusqlite::Error> for StateError {
+  fn from(e: rusqlite::Error) -> Self {
+    StateError::Sqlite(e)
+  }
+}
+
+/// Reads the file `name` from `tests/fixtures`.
+#[cfg(test)]
+fn read_fixture(name: &str) -> String {
+  let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+  std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Failed to read fixture '{}': {e}", path.display()))
+}
@@ -0,0 +1,141 @@
+use serenity::model::prelude::GuildId;
+use serenity::prelude::{RwLock, TypeMap, TypeMapKey};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{timeout_at, Instant};
+use crate::structures::State;
+use super::{StateError, Storage};
+
+/// How long to wait for further changes before writing, so that bursts of
+/// edits end up in a single write.
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// The longest a change waits to be written while further changes keep
+/// arriving.
+const MAX_SAVE_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Change {
+  /// The snippet `id` in `scope` was created, edited or removed.
+  Snippet { scope: Option<GuildId>, id: String },
+  /// The settings of `guild` were changed.
+  Settings { guild: GuildId },
+  /// The draft `id` in `guild` was proposed or reviewed.
+  Draft { guild: GuildId, id: u64 }
+}
+
+/// Writes state changes to storage from a background task.
+///
+/// Handlers only record what they changed with `mark`, the actual write
+/// happens once no change has been marked for `SAVE_DELAY`, or at the latest
+/// `MAX_SAVE_DELAY` after the first one, without holding the state lock
+/// during disk IO.
+pub struct Persistence {
+  storage: Arc<dyn Storage>,
+  changes: Mutex<HashSet<Change>>,
+  notify: Notify,
+  flush_lock: tokio::sync::Mutex<()>
+}
+
+pub struct PersistenceContainer;
+
+impl TypeMapKey for PersistenceContainer {
+  type Value = Arc<Persistence>;
+}
+
+impl Persistence {
+  pub fn new(storage: Arc<dyn Storage>) -> Persistence {
+    Self {
+      storage,
+      changes: Mutex::new(HashSet::new()),
+      notify: Notify::new(),
+      flush_lock: tokio::sync::Mutex::new(())
+    }
+  }
+
+  pub fn mark(&self, change: Change) {
+    self.changes.lock().expect("Failed to lock changes").insert(change);
+    self.notify.notify_one();
+  }
+
+  /// Spawns the task that flushes changes once they have settled.
+  pub fn spawn(self: &Arc<Self>, data: Arc<RwLock<TypeMap>>) {
+    let persistence = self.clone();
+
+    tokio::spawn(async move {
+      loop {
+        persistence.notify.notified().await;
+        let deadline = Instant::now() + MAX_SAVE_DELAY;
+
+        // Every change marked in the meantime restarts the delay
+        loop {
+          let settled = deadline.min(Instant::now() + SAVE_DELAY);
+
+          if timeout_at(settled, persistence.notify.notified()).await.is_err() {
+            break
+          }
+        }
+
+        persistence.flush(&data).await;
+      }
+    });
+  }
+
+  /// Replaces the state with the stored one if it was changed outside of the
+  /// bot, dropping the pending changes made to the old state so that they
+  /// cannot overwrite it. Holds the flush lock throughout, so that a write in
+  /// progress is never taken for an outside change. Returns the number of
+  /// snippets reloaded and of changes dropped.
+  pub async fn reload(&self, data: &RwLock<TypeMap>) -> Option<Result<(usize, usize), StateError>> {
+    let _guard = self.flush_lock.lock().await;
+
+    let storage = self.storage.clone();
+    let state = match tokio::task::spawn_blocking(move || storage.reload_if_changed()).await.expect("Failed to join storage task")? {
+      Ok(state) => state,
+      Err(e) => return Some(Err(e))
+    };
+
+    let mut data = data.write().await;
+    let snippets = state.snippet_count();
+    let dropped = self.changes.lock().expect("Failed to lock changes").drain().count();
+    data.insert::<State>(state);
+
+    Some(Ok((snippets, dropped)))
+  }
+
+  /// Writes every pending change, keeping them pending if the write fails so
+  /// that the next flush retries them.
+  pub async fn flush(&self, data: &RwLock<TypeMap>) {
+    let _guard = self.flush_lock.lock().await;
+
+    let changes: Vec<Change> = self.changes.lock()
+      .expect("Failed to lock changes")
+      .drain()
+      .collect();
+
+    if changes.is_empty() {
+      return
+    }
+
+    let state = {
+      let data = data.read().await;
+      data.get::<State>().expect("Failed to get state").clone()
+    };
+
+    let storage = self.storage.clone();
+    let (changes, result) = tokio::task::spawn_blocking(move || {
+      let result = storage.apply(&state, &changes);
+      (changes, result)
+    }).await.expect("Failed to join storage task");
+
+    match result {
+      Ok(_) => println!("Persisted {} state changes", changes.len()),
+      Err(e) => {
+        println!("Failed to persist {} state changes: {e}", changes.len());
+        self.changes.lock().expect("Failed to lock changes").extend(changes);
+      }
+    }
+  }
+}
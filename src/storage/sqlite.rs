@@ -0,0 +1,548 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Transaction};
+use serde_json::{from_str, from_value, json, to_string, Map, Value};
+use serenity::model::prelude::GuildId;
+use serenity::model::Timestamp;
+use std::path::Path;
+use std::sync::Mutex;
+use crate::structures::{AuditEntry, Draft, GuildSettings, Snippet, State, Usage, UsageCount, UsageSource};
+use super::migrations::{self, STATE_VERSION};
+use super::{AuditFilter, Change, StateError, Storage};
+
+/// The guild id global snippets and direct message audit entries are stored
+/// under, as no guild can have it.
+const GLOBAL_SCOPE: u64 = 0;
+
+/// `SCHEMA[n]` upgrades the database from `user_version` `n` to `n + 1`.
+const SCHEMA: [&str; 6] = [
+  "
+    CREATE TABLE IF NOT EXISTS meta (
+      key TEXT PRIMARY KEY NOT NULL,
+      value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS snippets (
+      id TEXT PRIMARY KEY NOT NULL,
+      data TEXT NOT NULL
+    );
+  ",
+  "
+    CREATE TABLE scoped_snippets (
+      guild_id INTEGER NOT NULL,
+      id TEXT NOT NULL,
+      data TEXT NOT NULL,
+      PRIMARY KEY (guild_id, id)
+    );
+    INSERT INTO scoped_snippets (guild_id, id, data) SELECT 0, id, data FROM snippets ORDER BY rowid;
+    DROP TABLE snippets;
+    ALTER TABLE scoped_snippets RENAME TO snippets;
+  ",
+  "
+    CREATE TABLE audit (
+      timestamp INTEGER NOT NULL,
+      actor INTEGER NOT NULL,
+      guild_id INTEGER NOT NULL,
+      snippet TEXT,
+      data TEXT NOT NULL
+    );
+    CREATE INDEX audit_guild_timestamp ON audit (guild_id, timestamp);
+  ",
+  "
+    CREATE TABLE usage (
+      timestamp INTEGER NOT NULL,
+      user INTEGER NOT NULL,
+      guild_id INTEGER NOT NULL,
+      channel INTEGER NOT NULL,
+      snippet TEXT NOT NULL,
+      source TEXT NOT NULL
+    );
+    CREATE INDEX usage_timestamp ON usage (timestamp);
+  ",
+  "
+    CREATE TABLE guild_settings (
+      guild_id INTEGER PRIMARY KEY NOT NULL,
+      data TEXT NOT NULL
+    );
+  ",
+  "
+    CREATE TABLE drafts (
+      guild_id INTEGER NOT NULL,
+      id INTEGER NOT NULL,
+      data TEXT NOT NULL,
+      PRIMARY KEY (guild_id, id)
+    );
+  "
+];
+
+/// Stores the state in an embedded SQLite database.
+///
+/// Snippets are kept as one JSON document per row, so that they go through
+/// the same migrations as the JSON backend and the table does not have to
+/// change whenever `Snippet` gains a field.
+pub struct SqliteStorage {
+  connection: Mutex<Connection>
+}
+
+impl SqliteStorage {
+  pub fn open(path: &Path) -> Result<SqliteStorage, StateError> {
+    let mut connection = Connection::open(path)?;
+    connection.pragma_update(None, "journal_mode", "WAL")?;
+
+    Self::upgrade_schema(&mut connection)?;
+    println!("Opened SQLite database '{}'", path.display());
+
+    Ok(Self { connection: Mutex::new(connection) })
+  }
+
+  fn upgrade_schema(connection: &mut Connection) -> Result<(), StateError> {
+    Self::upgrade_schema_to(connection, SCHEMA.len())
+  }
+
+  /// Applies the `SCHEMA` steps from the database's `user_version` up to
+  /// `target`.
+  fn upgrade_schema_to(connection: &mut Connection, target: usize) -> Result<(), StateError> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    for (from, schema) in SCHEMA.iter().enumerate().take(target).skip(version) {
+      let transaction = connection.transaction()?;
+      transaction.execute_batch(schema)?;
+      transaction.pragma_update(None, "user_version", from + 1)?;
+      transaction.commit()?;
+    }
+
+    Ok(())
+  }
+
+  fn read_versioned(&self) -> Result<(State, u64), StateError> {
+    let connection = self.connection.lock().expect("Failed to lock database");
+
+    let version: Option<String> = connection
+      .query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| row.get(0))
+      .optional()?;
+
+    let version = match version {
+      Some(version) => version.parse::<u64>()
+        .map_err(|e| StateError::Migration(format!("Invalid state version '{version}': {e}")))?,
+      None => STATE_VERSION
+    };
+
+    let mut global = Vec::new();
+    let mut guilds = Map::new();
+
+    let mut statement = connection.prepare("SELECT guild_id, data FROM snippets ORDER BY rowid")?;
+    let mut rows = statement.query([])?;
+
+    while let Some(row) = rows.next()? {
+      let guild_id: u64 = row.get(0)?;
+      let snippet: Value = from_str(&row.get::<_, String>(1)?)?;
+
+      if guild_id == GLOBAL_SCOPE {
+        global.push(snippet);
+      } else {
+        guilds.entry(guild_id.to_string())
+          .or_insert_with(|| json!({ "snippets": [] }))["snippets"]
+          .as_array_mut()
+          .expect("Must be an array")
+          .push(snippet);
+      }
+    }
+
+    let mut statement = connection.prepare("SELECT guild_id, data FROM guild_settings")?;
+    let mut rows = statement.query([])?;
+
+    while let Some(row) = rows.next()? {
+      let guild_id: u64 = row.get(0)?;
+      let settings: Value = from_str(&row.get::<_, String>(1)?)?;
+
+      guilds.entry(guild_id.to_string())
+        .or_insert_with(|| json!({ "snippets": [] }))["settings"] = settings;
+    }
+
+    let mut statement = connection.prepare("SELECT guild_id, data FROM drafts ORDER BY rowid")?;
+    let mut rows = statement.query([])?;
+
+    while let Some(row) = rows.next()? {
+      let guild_id: u64 = row.get(0)?;
+      let draft: Value = from_str(&row.get::<_, String>(1)?)?;
+
+      let guild = guilds.entry(guild_id.to_string())
+        .or_insert_with(|| json!({ "snippets": [] }));
+
+      match guild.get_mut("drafts").and_then(Value::as_array_mut) {
+        Some(drafts) => drafts.push(draft),
+        None => guild["drafts"] = json!([draft])
+      }
+    }
+
+    let (value, version) = migrations::migrate(json!({
+      "version": version,
+      "snippets": global,
+      "guilds": guilds
+    }))?;
+
+    Ok((from_value(value)?, version))
+  }
+
+  fn write_version(transaction: &Transaction) -> Result<(), StateError> {
+    transaction.execute(
+      "INSERT INTO meta (key, value) VALUES ('version', ?1)
+        ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+      params![STATE_VERSION.to_string()]
+    )?;
+
+    Ok(())
+  }
+
+  fn write_snippet(transaction: &Transaction, scope: Option<GuildId>, snippet: &Snippet) -> Result<(), StateError> {
+    transaction.execute(
+      "INSERT INTO snippets (guild_id, id, data) VALUES (?1, ?2, ?3)
+        ON CONFLICT (guild_id, id) DO UPDATE SET data = excluded.data",
+      params![scope_id(scope), snippet.id, to_string(snippet)?]
+    )?;
+
+    Ok(())
+  }
+
+  fn write_draft(transaction: &Transaction, guild: GuildId, draft: &Draft) -> Result<(), StateError> {
+    transaction.execute(
+      "INSERT INTO drafts (guild_id, id, data) VALUES (?1, ?2, ?3)
+        ON CONFLICT (guild_id, id) DO UPDATE SET data = excluded.data",
+      params![guild.0, draft.id, to_string(draft)?]
+    )?;
+
+    Ok(())
+  }
+
+  fn write_settings(transaction: &Transaction, guild: GuildId, settings: &GuildSettings) -> Result<(), StateError> {
+    transaction.execute(
+      "INSERT INTO guild_settings (guild_id, data) VALUES (?1, ?2)
+        ON CONFLICT (guild_id) DO UPDATE SET data = excluded.data",
+      params![guild.0, to_string(settings)?]
+    )?;
+
+    Ok(())
+  }
+}
+
+impl Storage for SqliteStorage {
+  fn name(&self) -> &'static str {
+    "sqlite"
+  }
+
+  fn load(&self) -> Result<State, StateError> {
+    let (state, version) = self.read_versioned()?;
+
+    if version < STATE_VERSION {
+      println!("Upgraded state from version {version} to {STATE_VERSION}");
+      self.save(&state)?;
+    }
+
+    Ok(state)
+  }
+
+  fn save(&self, state: &State) -> Result<(), StateError> {
+    let mut connection = self.connection.lock().expect("Failed to lock database");
+    let transaction = connection.transaction()?;
+
+    transaction.execute("DELETE FROM snippets", [])?;
+    transaction.execute("DELETE FROM guild_settings", [])?;
+    transaction.execute("DELETE FROM drafts", [])?;
+    for snippet in &state.snippets {
+      Self::write_snippet(&transaction, None, snippet)?;
+    }
+
+    for (guild, guild_state) in &state.guilds {
+      for snippet in &guild_state.snippets {
+        Self::write_snippet(&transaction, Some(*guild), snippet)?;
+      }
+
+      Self::write_settings(&transaction, *guild, &guild_state.settings)?;
+
+      for draft in &guild_state.drafts {
+        Self::write_draft(&transaction, *guild, draft)?;
+      }
+    }
+
+    Self::write_version(&transaction)?;
+    transaction.commit()?;
+
+    println!("Successfully saved {} snippets to the database", state.snippet_count());
+    Ok(())
+  }
+
+  fn apply(&self, state: &State, changes: &[Change]) -> Result<(), StateError> {
+    let mut connection = self.connection.lock().expect("Failed to lock database");
+    let transaction = connection.transaction()?;
+
+    for change in changes {
+      match change {
+        Change::Snippet { scope, id } => match state.scope(*scope).iter().find(|s| &s.id == id) {
+          Some(snippet) => Self::write_snippet(&transaction, *scope, snippet)?,
+          None => {
+            transaction.execute(
+              "DELETE FROM snippets WHERE guild_id = ?1 AND id = ?2",
+              params![scope_id(*scope), id]
+            )?;
+          }
+        },
+        Change::Settings { guild } => {
+          Self::write_settings(&transaction, *guild, &state.settings(*guild))?;
+        },
+        Change::Draft { guild, id } => match state.drafts(*guild).iter().find(|d| &d.id == id) {
+          Some(draft) => Self::write_draft(&transaction, *guild, draft)?,
+          None => {
+            transaction.execute(
+              "DELETE FROM drafts WHERE guild_id = ?1 AND id = ?2",
+              params![guild.0, id]
+            )?;
+          }
+        }
+      }
+    }
+
+    Self::write_version(&transaction)?;
+    transaction.commit()?;
+
+    println!("Successfully applied {} changes to the database", changes.len());
+    Ok(())
+  }
+
+  fn append_audit(&self, entry: &AuditEntry) -> Result<(), StateError> {
+    let connection = self.connection.lock().expect("Failed to lock database");
+    connection.execute(
+      "INSERT INTO audit (timestamp, actor, guild_id, snippet, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+      params![
+        entry.timestamp.unix_timestamp(),
+        entry.actor.0,
+        scope_id(entry.guild),
+        entry.snippet,
+        to_string(entry)?
+      ]
+    )?;
+
+    Ok(())
+  }
+
+  fn query_audit(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, StateError> {
+    let mut sql = "SELECT data FROM audit WHERE guild_id = ?".to_string();
+    let mut values: Vec<SqlValue> = vec![SqlValue::Integer(scope_id(filter.guild) as i64)];
+
+    if let Some(actor) = filter.actor {
+      sql.push_str(" AND actor = ?");
+      values.push(SqlValue::Integer(actor.0 as i64));
+    }
+
+    if let Some(snippet) = &filter.snippet {
+      sql.push_str(" AND snippet = ?");
+      values.push(SqlValue::Text(snippet.clone()));
+    }
+
+    if let Some(since) = filter.since {
+      sql.push_str(" AND timestamp >= ?");
+      values.push(SqlValue::Integer(since.unix_timestamp()));
+    }
+
+    if let Some(until) = filter.until {
+      sql.push_str(" AND timestamp < ?");
+      values.push(SqlValue::Integer(until.unix_timestamp()));
+    }
+
+    sql.push_str(" ORDER BY rowid DESC LIMIT ?");
+    values.push(SqlValue::Integer(filter.limit as i64));
+
+    let connection = self.connection.lock().expect("Failed to lock database");
+    let mut statement = connection.prepare(&sql)?;
+    let entries = statement.query_map(params_from_iter(values), |row| row.get::<_, String>(0))?
+      .map(|data| Ok(from_str::<AuditEntry>(&data?)?))
+      .collect::<Result<Vec<AuditEntry>, StateError>>()?;
+
+    Ok(entries)
+  }
+
+  fn append_usage(&self, usage: &Usage) -> Result<(), StateError> {
+    let connection = self.connection.lock().expect("Failed to lock database");
+    connection.execute(
+      "INSERT INTO usage (timestamp, user, guild_id, channel, snippet, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+      params![
+        usage.timestamp.unix_timestamp(),
+        usage.user.0,
+        scope_id(usage.guild),
+        usage.channel.0,
+        usage.snippet,
+        usage.source.name()
+      ]
+    )?;
+
+    Ok(())
+  }
+
+  fn count_usage(&self, since: Option<Timestamp>) -> Result<Vec<UsageCount>, StateError> {
+    let since = since.map(|s| s.unix_timestamp()).unwrap_or(i64::MIN);
+
+    let connection = self.connection.lock().expect("Failed to lock database");
+    let mut statement = connection.prepare(
+      "SELECT guild_id, snippet, source, COUNT(*) FROM usage WHERE timestamp >= ?1 GROUP BY guild_id, snippet, source"
+    )?;
+
+    let rows = statement.query_map(params![since], |row| Ok((
+      row.get::<_, u64>(0)?,
+      row.get::<_, String>(1)?,
+      row.get::<_, String>(2)?,
+      row.get::<_, u64>(3)?
+    )))?;
+
+    let mut counts = Vec::new();
+    for row in rows {
+      let (guild_id, snippet, source, count) = row?;
+
+      match UsageSource::from_name(&source) {
+        Some(source) => counts.push(UsageCount {
+          guild: (guild_id != GLOBAL_SCOPE).then_some(GuildId(guild_id)),
+          snippet,
+          source,
+          count
+        }),
+        None => println!("Skipping usage with unknown source '{source}'")
+      }
+    }
+
+    Ok(counts)
+  }
+}
+
+fn scope_id(scope: Option<GuildId>) -> u64 {
+  scope.map(|g| g.0).unwrap_or(GLOBAL_SCOPE)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use serenity::model::prelude::RoleId;
+  use crate::structures::SnippetAction;
+  use super::super::read_fixture;
+  use super::*;
+
+  /// An in-memory database at the schema of the fixture `name`.
+  fn open_fixture(name: &str) -> Connection {
+    let connection = Connection::open_in_memory().expect("Failed to open database");
+    connection.execute_batch(&read_fixture(name)).expect("Failed to load fixture");
+    connection
+  }
+
+  /// Writes the fixture `name` into a new database file at `path`.
+  fn open_fixture_at(path: &Path, name: &str) {
+    let _ = fs::remove_file(path);
+    let connection = Connection::open(path).expect("Failed to open database");
+    connection.execute_batch(&read_fixture(name)).expect("Failed to load fixture");
+  }
+
+  fn user_version(connection: &Connection) -> usize {
+    connection.pragma_query_value(None, "user_version", |row| row.get(0)).expect("Failed to get user_version")
+  }
+
+  fn columns(connection: &Connection, table: &str) -> Vec<String> {
+    let mut statement = connection.prepare("SELECT name FROM pragma_table_info(?1)").expect("Failed to prepare");
+    let columns = statement.query_map([table], |row| row.get(0)).expect("Failed to query")
+      .collect::<Result<Vec<String>, rusqlite::Error>>()
+      .expect("Failed to read columns");
+
+    columns
+  }
+
+  fn count(connection: &Connection, table: &str) -> usize {
+    connection.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0)).expect("Failed to count rows")
+  }
+
+  #[test]
+  fn schema_0_creates_tables() {
+    let mut connection = Connection::open_in_memory().expect("Failed to open database");
+    SqliteStorage::upgrade_schema_to(&mut connection, 1).expect("Failed to upgrade");
+
+    assert_eq!(user_version(&connection), 1);
+    assert_eq!(columns(&connection, "meta"), ["key", "value"]);
+    assert_eq!(columns(&connection, "snippets"), ["id", "data"]);
+  }
+
+  #[test]
+  fn schema_1_scopes_snippets() {
+    let mut connection = open_fixture("sqlite-v1.sql");
+    SqliteStorage::upgrade_schema_to(&mut connection, 2).expect("Failed to upgrade");
+
+    assert_eq!(user_version(&connection), 2);
+    assert_eq!(columns(&connection, "snippets"), ["guild_id", "id", "data"]);
+
+    let mut statement = connection.prepare("SELECT guild_id, id FROM snippets ORDER BY rowid").expect("Failed to prepare");
+    let snippets = statement.query_map([], |row| Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?)))
+      .expect("Failed to query")
+      .collect::<Result<Vec<(u64, String)>, rusqlite::Error>>()
+      .expect("Failed to read snippets");
+
+    assert_eq!(snippets, [(GLOBAL_SCOPE, "logs".to_string()), (GLOBAL_SCOPE, "faq".to_string())]);
+  }
+
+  #[test]
+  fn schema_2_adds_audit() {
+    let mut connection = open_fixture("sqlite-v2.sql");
+    SqliteStorage::upgrade_schema_to(&mut connection, 3).expect("Failed to upgrade");
+
+    assert_eq!(user_version(&connection), 3);
+    assert_eq!(columns(&connection, "audit"), ["timestamp", "actor", "guild_id", "snippet", "data"]);
+    assert_eq!(count(&connection, "snippets"), 2);
+  }
+
+  #[test]
+  fn schema_3_adds_usage() {
+    let mut connection = open_fixture("sqlite-v3.sql");
+    SqliteStorage::upgrade_schema_to(&mut connection, 4).expect("Failed to upgrade");
+
+    assert_eq!(user_version(&connection), 4);
+    assert_eq!(columns(&connection, "usage"), ["timestamp", "user", "guild_id", "channel", "snippet", "source"]);
+    assert_eq!(count(&connection, "audit"), 1);
+  }
+
+  #[test]
+  fn schema_4_adds_guild_settings() {
+    let mut connection = open_fixture("sqlite-v4.sql");
+    SqliteStorage::upgrade_schema_to(&mut connection, 5).expect("Failed to upgrade");
+
+    assert_eq!(user_version(&connection), 5);
+    assert_eq!(columns(&connection, "guild_settings"), ["guild_id", "data"]);
+    assert_eq!(count(&connection, "usage"), 1);
+  }
+
+  #[test]
+  fn schema_5_adds_drafts() {
+    let mut connection = open_fixture("sqlite-v5.sql");
+    SqliteStorage::upgrade_schema_to(&mut connection, 6).expect("Failed to upgrade");
+
+    assert_eq!(user_version(&connection), 6);
+    assert_eq!(columns(&connection, "drafts"), ["guild_id", "id", "data"]);
+    assert_eq!(count(&connection, "guild_settings"), 1);
+  }
+
+  #[test]
+  fn loads_every_schema_version() {
+    for version in 1..SCHEMA.len() {
+      let path = std::env::temp_dir().join(format!("tabletbot-test-{}-v{version}.db", std::process::id()));
+      open_fixture_at(&path, &format!("sqlite-v{version}.sql"));
+
+      let state = SqliteStorage::open(&path).and_then(|s| s.load()).expect("Failed to load state");
+
+      for suffix in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+      }
+
+      assert_eq!(state.version, migrations::STATE_VERSION);
+      assert!(state.snippets.iter().any(|s| s.id == "faq"), "Missing global snippet at v{version}");
+
+      if version >= 2 {
+        let guild = state.guilds.get(&GuildId(1000)).expect("Missing guild");
+        assert_eq!(guild.snippets[0].id, "rules");
+      }
+
+      if version >= 5 {
+        let roles = state.settings(GuildId(1000)).roles;
+        assert_eq!(roles.get(&SnippetAction::Create), Some(&vec![RoleId(5)]));
+      }
+    }
+  }
+}
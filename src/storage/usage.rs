@@ -0,0 +1,77 @@
+use serenity::prelude::{RwLock, TypeMap, TypeMapKey};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use crate::structures::{Usage, UsageCounts};
+use super::Storage;
+
+/// Writes shown snippets to storage from a background task.
+///
+/// Handlers only queue each display with `record`, the task then counts the
+/// queued displays for search ranking and writes them, so that showing a
+/// snippet neither waits for the state lock nor for disk IO.
+pub struct UsageLog {
+  storage: Arc<dyn Storage>,
+  pending: Mutex<Vec<Usage>>,
+  notify: Notify
+}
+
+pub struct UsageLogContainer;
+
+impl TypeMapKey for UsageLogContainer {
+  type Value = Arc<UsageLog>;
+}
+
+impl UsageLog {
+  pub fn new(storage: Arc<dyn Storage>) -> UsageLog {
+    Self {
+      storage,
+      pending: Mutex::new(Vec::new()),
+      notify: Notify::new()
+    }
+  }
+
+  pub fn record(&self, usage: Usage) {
+    self.pending.lock().expect("Failed to lock usage").push(usage);
+    self.notify.notify_one();
+  }
+
+  /// Spawns the task that writes queued usage as it arrives.
+  pub fn spawn(self: &Arc<Self>, data: Arc<RwLock<TypeMap>>) {
+    let usage = self.clone();
+
+    tokio::spawn(async move {
+      loop {
+        usage.notify.notified().await;
+        usage.flush(&data).await;
+      }
+    });
+  }
+
+  /// Counts and writes every queued usage, dropping the ones that fail to be
+  /// written as statistics do not need to be exact.
+  pub async fn flush(&self, data: &RwLock<TypeMap>) {
+    let pending: Vec<Usage> = std::mem::take(&mut *self.pending.lock().expect("Failed to lock usage"));
+
+    if pending.is_empty() {
+      return
+    }
+
+    {
+      let mut data = data.write().await;
+      let counts = data.get_mut::<UsageCounts>().expect("Failed to get usage counts");
+
+      for usage in &pending {
+        *counts.entry(usage.guild).or_default().entry(usage.snippet.clone()).or_default() += 1;
+      }
+    }
+
+    let storage = self.storage.clone();
+    tokio::task::spawn_blocking(move || {
+      for usage in pending {
+        if let Err(e) = storage.append_usage(&usage) {
+          println!("Failed to record usage of '{}': {e}", usage.snippet);
+        }
+      }
+    }).await.expect("Failed to join storage task")
+  }
+}
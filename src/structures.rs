@@ -1,12 +1,12 @@
-use serde_json::{from_reader, to_writer_pretty};
 use serde::{Deserialize, Serialize};
 use serenity::builder::CreateEmbed;
 use serenity::client::bridge::gateway::ShardManager;
+use serenity::model::prelude::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use serenity::model::Timestamp;
 use serenity::prelude::{TypeMapKey, Mutex};
-use std::env;
-use std::fs::{self, File, OpenOptions };
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
+use crate::storage::migrations::STATE_VERSION;
 
 pub struct ShardManagerContainer;
 
@@ -14,26 +14,480 @@ impl TypeMapKey for ShardManagerContainer {
   type Value = Arc<Mutex<ShardManager>>;
 }
 
+pub struct Owners;
+
+impl TypeMapKey for Owners {
+  type Value = HashSet<UserId>;
+}
+
 pub trait Embeddable {
   fn embed(&self) -> CreateEmbed;
 }
 
-#[derive(Deserialize, Serialize, Clone)]
+/// The number of revisions kept per snippet, older ones are dropped.
+const MAX_REVISIONS: usize = 50;
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
 pub struct Snippet {
   pub id: String,
+  pub title: String,
+  pub content: String,
+  /// Other names the snippet can be found by.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub aliases: Vec<String>,
+  /// The topic the snippet is listed under, such as "Linux" or "Plugins".
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub category: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub tags: Vec<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub fields: Vec<SnippetField>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub image: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub thumbnail: Option<String>,
+  /// The embed colour as `0xRRGGBB`, the accent colour if `None`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub colour: Option<u32>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub footer: Option<String>,
+  /// Buttons below the snippet that open a URL.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub links: Vec<SnippetLink>,
+  /// Translations of the title and content by locale, such as "de" or "pt-BR".
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub variants: BTreeMap<String, SnippetVariant>,
+  /// Sections shown after the content to users of each OS.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub platforms: BTreeMap<Platform, String>,
+  /// Previous versions of the snippet, oldest first.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub revisions: Vec<Revision>
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct SnippetField {
+  pub name: String,
+  pub value: String,
+  #[serde(default)]
+  pub inline: bool
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct SnippetLink {
+  pub label: String,
+  pub url: String
+}
+
+/// The title and content of a snippet in another language.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct SnippetVariant {
   pub title: String,
   pub content: String
 }
 
-#[derive(Deserialize, Serialize)]
+/// A change to a snippet, holding the title and content it replaced.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct Revision {
+  /// Counts up from 1 for each snippet and is never reused, so a number
+  /// keeps naming the same revision once older ones are dropped.
+  pub number: u64,
+  pub author: UserId,
+  pub timestamp: Timestamp,
+  pub title: String,
+  pub content: String
+}
+
+impl Snippet {
+  pub fn new(id: String, title: String, content: String) -> Snippet {
+    Self {
+      id,
+      title,
+      content,
+      aliases: Vec::new(),
+      category: None,
+      tags: Vec::new(),
+      fields: Vec::new(),
+      image: None,
+      thumbnail: None,
+      colour: None,
+      footer: None,
+      links: Vec::new(),
+      variants: BTreeMap::new(),
+      platforms: BTreeMap::new(),
+      revisions: Vec::new()
+    }
+  }
+
+  /// Checks that the snippet can be shown within Discord's embed and button
+  /// limits, describing the first problem found.
+  pub fn validate(&self) -> Result<(), String> {
+    for (locale, variant) in &self.variants {
+      if !is_locale(locale) {
+        return Err(format!("'{locale}' is not a locale such as de or pt-BR"))
+      }
+
+      let localized = Self {
+        title: variant.title.clone(),
+        content: variant.content.clone(),
+        variants: BTreeMap::new(),
+        ..self.clone()
+      };
+
+      localized.validate().map_err(|e| format!("{e} in the '{locale}' translation"))?;
+    }
+
+    for platform in self.platforms.keys() {
+      let section = Self { platforms: BTreeMap::new(), ..self.for_platform(*platform) };
+      section.validate().map_err(|e| format!("{e} with the {} section", platform.label()))?;
+    }
+
+    // The OS tabs take up one of the five rows of buttons
+    if !self.platforms.is_empty() && self.links.len() > 20 {
+      return Err(format!("There are {} links, the limit is 20 for a snippet with OS sections", self.links.len()))
+    }
+
+    let lengths = [
+      ("title", self.title.chars().count(), 256),
+      ("content", self.content.chars().count(), 4096),
+      ("footer", self.footer.as_deref().unwrap_or_default().chars().count(), 2048)
+    ];
+
+    for (name, length, limit) in lengths {
+      if length > limit {
+        return Err(format!("The {name} is {length} characters long, the limit is {limit}"))
+      }
+    }
+
+    if self.fields.len() > 25 {
+      return Err(format!("There are {} fields, the limit is 25", self.fields.len()))
+    }
+
+    for field in &self.fields {
+      if field.name.is_empty() || field.name.chars().count() > 256 {
+        return Err(format!("The field name '{}' must be between 1 and 256 characters long", field.name))
+      }
+
+      if field.value.is_empty() || field.value.chars().count() > 1024 {
+        return Err(format!("The value of the field '{}' must be between 1 and 1024 characters long", field.name))
+      }
+    }
+
+    let total = self.title.chars().count()
+      + self.content.chars().count()
+      + self.footer.as_deref().unwrap_or_default().chars().count()
+      + self.fields.iter().map(|f| f.name.chars().count() + f.value.chars().count()).sum::<usize>();
+
+    if total > 6000 {
+      return Err(format!("The embed is {total} characters long in total, the limit is 6000"))
+    }
+
+    if self.links.len() > 25 {
+      return Err(format!("There are {} links, the limit is 25", self.links.len()))
+    }
+
+    for link in &self.links {
+      if link.label.is_empty() || link.label.chars().count() > 80 {
+        return Err(format!("The link label '{}' must be between 1 and 80 characters long", link.label))
+      }
+    }
+
+    let urls = self.image.iter()
+      .chain(self.thumbnail.iter())
+      .chain(self.links.iter().map(|l| &l.url));
+
+    for url in urls {
+      match url.parse::<reqwest::Url>() {
+        Ok(parsed) if ["http", "https"].contains(&parsed.scheme()) => (),
+        _ => return Err(format!("'{url}' is not a valid http or https URL"))
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Whether `name` is the snippet's id or one of its aliases.
+  pub fn matches(&self, name: &str) -> bool {
+    self.id == name || self.aliases.iter().any(|a| a == name)
+  }
+
+  /// Whether the snippet is in `category` and has `tag`, ignoring case.
+  /// `None` matches any category or tag.
+  pub fn is_listed(&self, category: Option<&str>, tag: Option<&str>) -> bool {
+    let in_category = category.map_or(true, |c| self.category.as_ref().is_some_and(|s| s.eq_ignore_ascii_case(c)));
+    let has_tag = tag.map_or(true, |t| self.tags.iter().any(|s| s.eq_ignore_ascii_case(t)));
+
+    in_category && has_tag
+  }
+
+  /// The id followed by every alias.
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    std::iter::once(self.id.as_str()).chain(self.aliases.iter().map(String::as_str))
+  }
+
+  /// The locale of the translation best matching `locale`, trying the locale
+  /// itself then its language, such as "pt" for "pt-BR", ignoring case.
+  pub fn find_variant(&self, locale: &str) -> Option<&str> {
+    let language = locale.split('-').next().unwrap_or(locale);
+
+    [locale, language].into_iter()
+      .find_map(|l| self.variants.keys().find(|k| k.eq_ignore_ascii_case(l)))
+      .map(String::as_str)
+  }
+
+  /// A copy of the snippet showing the translation best matching `locale`,
+  /// or the default title and content if there is none.
+  pub fn localized(&self, locale: &str) -> Snippet {
+    match self.find_variant(locale).and_then(|l| self.variants.get(l)) {
+      Some(variant) => Self {
+        title: variant.title.clone(),
+        content: variant.content.clone(),
+        ..self.clone()
+      },
+      None => self.clone()
+    }
+  }
+
+  /// A copy of the snippet with the section for `platform`, if it has one,
+  /// shown after the content.
+  pub fn for_platform(&self, platform: Platform) -> Snippet {
+    let content = match self.platforms.get(&platform) {
+      Some(section) if self.content.is_empty() => section.clone(),
+      Some(section) => format!("{}\n\n{section}", self.content),
+      None => self.content.clone()
+    };
+
+    Self { content, ..self.clone() }
+  }
+
+  /// A copy of the snippet without its revisions, for recording elsewhere.
+  pub fn snapshot(&self) -> Snippet {
+    Self { revisions: Vec::new(), ..self.clone() }
+  }
+
+  /// Replaces the title and content, recording the previous ones as a
+  /// revision made by `author`.
+  pub fn revise(&mut self, author: UserId, title: String, content: String) {
+    self.revisions.push(Revision {
+      number: self.revisions.last().map_or(1, |r| r.number + 1),
+      author,
+      timestamp: Timestamp::now(),
+      title: std::mem::replace(&mut self.title, title),
+      content: std::mem::replace(&mut self.content, content)
+    });
+
+    if self.revisions.len() > MAX_REVISIONS {
+      self.revisions.remove(0);
+    }
+  }
+}
+
+/// Whether `locale` looks like a Discord locale, a language code optionally
+/// followed by a region such as "pt-BR".
+fn is_locale(locale: &str) -> bool {
+  let (language, region) = match locale.split_once('-') {
+    Some((language, region)) => (language, Some(region)),
+    None => (locale, None)
+  };
+
+  (2..=3).contains(&language.len())
+    && language.chars().all(|c| c.is_ascii_alphabetic())
+    && region.map_or(true, |r| (2..=4).contains(&r.len()) && r.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// A record of a change made to the state, kept in an append-only log.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AuditEntry {
+  pub timestamp: Timestamp,
+  pub actor: UserId,
+  /// The guild the command was used in, `None` for direct messages.
+  pub guild: Option<GuildId>,
+  pub command: String,
+  /// The id of the snippet that was changed, if the command changed one.
+  pub snippet: Option<String>,
+  pub before: Option<Snippet>,
+  pub after: Option<Snippet>,
+  /// What was changed, for changes to a guild's settings rather than to a
+  /// snippet.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub details: Option<String>
+}
+
+/// A snippet being shown, recorded for usage statistics.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Usage {
+  pub timestamp: Timestamp,
+  pub user: UserId,
+  /// The guild the snippet was shown in, `None` for direct messages.
+  pub guild: Option<GuildId>,
+  pub channel: ChannelId,
+  pub snippet: String,
+  pub source: UsageSource
+}
+
+/// The way a snippet was shown.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum UsageSource {
+  SlashCommand,
+  TextCommand,
+  Search,
+  Reply
+}
+
+/// The number of times a snippet was shown in a guild through one source.
+pub struct UsageCount {
+  pub guild: Option<GuildId>,
+  pub snippet: String,
+  pub source: UsageSource,
+  pub count: u64
+}
+
+/// How often each snippet was shown in each guild, by snippet id, used to
+/// rank snippets that match a search equally well.
+pub struct UsageCounts;
+
+impl TypeMapKey for UsageCounts {
+  type Value = HashMap<Option<GuildId>, HashMap<String, u64>>;
+}
+
+impl Usage {
+  pub fn new(user: UserId, guild: Option<GuildId>, channel: ChannelId, snippet: String, source: UsageSource) -> Usage {
+    Self { timestamp: Timestamp::now(), user, guild, channel, snippet, source }
+  }
+}
+
+impl UsageSource {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::SlashCommand => "slash-command",
+      Self::TextCommand => "text-command",
+      Self::Search => "search",
+      Self::Reply => "reply"
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<UsageSource> {
+    [Self::SlashCommand, Self::TextCommand, Self::Search, Self::Reply].into_iter()
+      .find(|s| s.name() == name)
+  }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
 pub struct State {
-  pub snippets: Vec<Snippet>
+  pub version: u64,
+  /// Snippets shared by every guild.
+  pub snippets: Vec<Snippet>,
+  pub guilds: BTreeMap<GuildId, GuildState>
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct GuildState {
+  pub snippets: Vec<Snippet>,
+  #[serde(default)]
+  pub settings: GuildSettings,
+  /// Proposed snippets waiting for review.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub drafts: Vec<Draft>
+}
+
+/// How a guild has configured the bot.
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct GuildSettings {
+  /// The roles allowed to take each action, anyone may take an action
+  /// without any.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub roles: BTreeMap<SnippetAction, Vec<RoleId>>,
+  /// The channel drafts are posted in for review.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub review_channel: Option<ChannelId>,
+  /// The roles that tell which OS a member uses, to pick the section of a
+  /// snippet shown for them.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub platforms: BTreeMap<Platform, Vec<RoleId>>
+}
+
+/// A proposed new snippet or edit, which is only applied once approved.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct Draft {
+  pub id: u64,
+  pub author: UserId,
+  pub timestamp: Timestamp,
+  /// The snippet as it would be once approved.
+  pub snippet: Snippet,
+  /// The snippet when the draft was proposed, `None` for a new snippet.
+  pub base: Option<Snippet>,
+  /// The message the draft was posted for review in.
+  pub message: Option<MessageId>
+}
+
+/// A kind of change to snippets that a guild can restrict to some roles.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnippetAction {
+  Create,
+  Edit,
+  Remove,
+  Propose
+}
+
+impl SnippetAction {
+  pub const ALL: [SnippetAction; 4] = [Self::Create, Self::Edit, Self::Remove, Self::Propose];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Create => "create",
+      Self::Edit => "edit",
+      Self::Remove => "remove",
+      Self::Propose => "propose"
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<SnippetAction> {
+    Self::ALL.into_iter().find(|a| a.name() == name)
+  }
+}
+
+/// An operating system a snippet can have a section for.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Platform {
+  Windows,
+  Linux,
+  MacOS
+}
+
+impl Platform {
+  pub const ALL: [Platform; 3] = [Self::Windows, Self::Linux, Self::MacOS];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Windows => "windows",
+      Self::Linux => "linux",
+      Self::MacOS => "macos"
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Platform> {
+    Self::ALL.into_iter().find(|p| p.name() == name)
+  }
+
+  /// The OS's name as written in messages.
+  pub fn label(&self) -> &'static str {
+    match self {
+      Self::Windows => "Windows",
+      Self::Linux => "Linux",
+      Self::MacOS => "macOS"
+    }
+  }
 }
 
 impl Default for State {
   fn default() -> State {
     Self {
-      snippets: Vec::new()
+      version: STATE_VERSION,
+      snippets: Vec::new(),
+      guilds: BTreeMap::new()
     }
   }
 }
@@ -43,48 +497,105 @@ impl TypeMapKey for State {
 }
 
 impl State {
-  pub fn get_path() -> String {
-    let pwd = env::current_dir().unwrap().to_string_lossy().to_string();
-    let data_root = env::var("TABLETBOT_DATA").unwrap_or(pwd);
+  /// The snippets visible in `guild`, where a guild's own snippets shadow
+  /// global snippets with the same id. `None` only sees global snippets.
+  pub fn snippets(&self, guild: Option<GuildId>) -> Vec<&Snippet> {
+    let local = guild
+      .and_then(|g| self.guilds.get(&g))
+      .map(|g| g.snippets.as_slice())
+      .unwrap_or_default();
+
+    let global = self.snippets.iter()
+      .filter(|s| !local.iter().any(|l| l.id == s.id));
+
+    local.iter().chain(global).collect()
+  }
+
+  /// Checks that no scope has two snippets sharing an id or alias and that
+  /// every snippet can be shown, describing the first problem found.
+  pub fn validate(&self) -> Result<(), String> {
+    let scopes = std::iter::once((None, &self.snippets))
+      .chain(self.guilds.iter().map(|(guild, state)| (Some(*guild), &state.snippets)));
+
+    for (scope, snippets) in scopes {
+      let place = match scope {
+        Some(guild) => format!("guild {guild}"),
+        None => "the global snippets".to_string()
+      };
+
+      let mut names = HashSet::new();
+
+      for snippet in snippets {
+        snippet.validate().map_err(|e| format!("The snippet '{}' in {place} is invalid: {e}", snippet.id))?;
 
-    match env::var("TABLETBOT_STATE") {
-      Ok(path) => path,
-      Err(_) => format!("{data_root}/state.json")
+        if let Some(name) = snippet.names().find(|name| !names.insert(*name)) {
+          return Err(format!("'{name}' is used by more than one snippet in {place}"))
+        }
+      }
     }
+
+    Ok(())
   }
 
-  pub fn read() -> State {
-    let path_str = Self::get_path();
-    let path = Path::new(&path_str);
+  /// The number of snippets across every scope.
+  pub fn snippet_count(&self) -> usize {
+    self.snippets.len() + self.guilds.values().map(|g| g.snippets.len()).sum::<usize>()
+  }
 
-    if path.exists() {
-      let file = File::open(path).unwrap();
-      from_reader(file).unwrap()
-    } else {
-      State::default()
+  /// The snippet visible in `guild` with the id or alias `name`.
+  pub fn get_snippet(&self, guild: Option<GuildId>, name: &str) -> Option<&Snippet> {
+    self.snippets(guild).into_iter().find(|s| s.matches(name))
+  }
+
+  /// The snippets stored in `scope`, which is either a guild or the global
+  /// scope if `None`.
+  pub fn scope(&self, scope: Option<GuildId>) -> &[Snippet] {
+    match scope {
+      Some(guild) => self.guilds.get(&guild)
+        .map(|g| g.snippets.as_slice())
+        .unwrap_or_default(),
+      None => &self.snippets
     }
   }
 
-  pub fn write(&self) {
-    let path_str = Self::get_path();
-    let path = Path::new(&path_str);
+  /// The snippet in `scope` other than `except` that already uses `name` as
+  /// its id or as an alias.
+  pub fn find_conflict(&self, scope: Option<GuildId>, name: &str, except: &str) -> Option<&Snippet> {
+    self.scope(scope).iter().find(|s| s.id != except && s.matches(name))
+  }
 
-    if path.exists() {
-      fs::remove_file(path).expect("Failed to delete old file.");
+  pub fn scope_mut(&mut self, scope: Option<GuildId>) -> &mut Vec<Snippet> {
+    match scope {
+      Some(guild) => &mut self.guilds.entry(guild).or_default().snippets,
+      None => &mut self.snippets
     }
+  }
 
-    let writer = OpenOptions::new()
-      .read(true)
-      .write(true)
-      .create(true)
-      .open(&path);
+  /// The settings of `guild`, the defaults if it has none.
+  pub fn settings(&self, guild: GuildId) -> GuildSettings {
+    self.guilds.get(&guild).map(|g| g.settings.clone()).unwrap_or_default()
+  }
 
-    match writer {
-      Ok(writer) => match to_writer_pretty(writer, self) {
-        Ok(_) => println!("Successfully saved state to '{path_str}'", ),
-        Err(e) => println!("Failed to save state to '{path_str}': {e}")
-      },
-      Err(e) => println!("Unable to write state to '{path_str}': {e}")
-    };
+  pub fn settings_mut(&mut self, guild: GuildId) -> &mut GuildSettings {
+    &mut self.guilds.entry(guild).or_default().settings
+  }
+
+  pub fn drafts(&self, guild: GuildId) -> &[Draft] {
+    self.guilds.get(&guild).map(|g| g.drafts.as_slice()).unwrap_or_default()
+  }
+
+  pub fn drafts_mut(&mut self, guild: GuildId) -> &mut Vec<Draft> {
+    &mut self.guilds.entry(guild).or_default().drafts
+  }
+
+  /// The scope the id or alias `name` resolves to in `guild`, if it exists.
+  pub fn find_scope(&self, guild: Option<GuildId>, name: &str) -> Option<Option<GuildId>> {
+    if guild.is_some() && self.scope(guild).iter().any(|s| s.matches(name)) {
+      Some(guild)
+    } else if self.snippets.iter().any(|s| s.matches(name)) {
+      Some(None)
+    } else {
+      None
+    }
   }
 }
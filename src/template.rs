@@ -0,0 +1,251 @@
+use serenity::model::prelude::{ChannelId, UserId};
+use std::fmt::Display;
+use crate::events::issue::{REPO_NAME, REPO_OWNER};
+use crate::structures::Snippet;
+
+/// The values placeholders in snippets are replaced with.
+///
+/// Snippets may use `{user}`, `{channel}` and `{latest_release}`, as well as
+/// the positional arguments `{1}`, `{2}` and so on, where `{1|default}` makes
+/// an argument optional. `{{user}}` shows the placeholder as `{user}`, and
+/// any other braces are left as they are.
+#[derive(Clone)]
+pub struct Variables {
+  user: UserId,
+  channel: ChannelId,
+  args: Vec<String>,
+  latest_release: Option<String>
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+  /// Argument `index` was used without a default, but only `given` were passed.
+  MissingArgument { index: usize, given: usize },
+  Unavailable(&'static str)
+}
+
+impl Display for TemplateError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::MissingArgument { index, given } => write!(f,
+        "Argument {index} of this snippet is missing ({given} given), pass arguments with the args option, e.g. `args: \"Wacom CTL-480\" linux`"
+      ),
+      Self::Unavailable(name) => write!(f, "The value of {{{name}}} is currently unavailable")
+    }
+  }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl Variables {
+  pub fn new(user: UserId, channel: ChannelId, args: Vec<String>) -> Variables {
+    Self { user, channel, args, latest_release: None }
+  }
+}
+
+/// Renders the text of `snippet`, fetching the values of the placeholders it
+/// uses that are not known up front.
+pub async fn render_snippet(snippet: &Snippet, mut variables: Variables) -> Result<Snippet, TemplateError> {
+  let uses_release = [&snippet.title, &snippet.content].into_iter()
+    .chain(snippet.footer.iter())
+    .chain(snippet.fields.iter().flat_map(|f| [&f.name, &f.value]))
+    .any(|text| text.contains("{latest_release"));
+
+  if uses_release {
+    variables.latest_release = latest_release().await;
+  }
+
+  let mut rendered = snippet.clone();
+  rendered.title = render(&snippet.title, &variables)?;
+  rendered.content = render(&snippet.content, &variables)?;
+  rendered.footer = snippet.footer.as_deref().map(|f| render(f, &variables)).transpose()?;
+
+  for field in &mut rendered.fields {
+    field.name = render(&field.name, &variables)?;
+    field.value = render(&field.value, &variables)?;
+  }
+
+  Ok(rendered)
+}
+
+pub fn render(text: &str, variables: &Variables) -> Result<String, TemplateError> {
+  let mut output = String::with_capacity(text.len());
+  let mut rest = text;
+
+  while let Some(i) = rest.find('{') {
+    output.push_str(&rest[..i]);
+    let after = &rest[i + 1..];
+
+    // `{{name}}` shows the placeholder itself, other doubled braces are kept
+    let escaped = after.strip_prefix('{')
+      .and_then(|inner| inner.split_once("}}"))
+      .filter(|(name, _)| is_placeholder(name));
+
+    if let Some((name, remaining)) = escaped {
+      output.push_str(&format!("{{{name}}}"));
+      rest = remaining;
+      continue
+    }
+
+    let placeholder = after.find('}').map(|end| (&after[..end], &after[end + 1..]));
+
+    match placeholder {
+      Some((name, remaining)) => match resolve(name, variables)? {
+        Some(value) => {
+          output.push_str(&value);
+          rest = remaining;
+        },
+        None => {
+          output.push('{');
+          rest = after;
+        }
+      },
+      None => {
+        output.push('{');
+        rest = after;
+      }
+    }
+  }
+
+  output.push_str(rest);
+  Ok(output)
+}
+
+/// Whether `name` is the name of a placeholder, with or without a default.
+fn is_placeholder(name: &str) -> bool {
+  let name = name.split_once('|').map_or(name, |(name, _)| name);
+  matches!(name, "user" | "channel" | "latest_release") || matches!(name.parse::<usize>(), Ok(index) if index > 0)
+}
+
+/// The value of the placeholder `name`, or `None` if it is not a placeholder.
+fn resolve(name: &str, variables: &Variables) -> Result<Option<String>, TemplateError> {
+  let (name, default) = match name.split_once('|') {
+    Some((name, default)) => (name, Some(default)),
+    None => (name, None)
+  };
+
+  match name {
+    "user" => Ok(Some(format!("<@{}>", variables.user))),
+    "channel" => Ok(Some(format!("<#{}>", variables.channel))),
+    "latest_release" => match (&variables.latest_release, default) {
+      (Some(release), _) => Ok(Some(release.clone())),
+      (None, Some(default)) => Ok(Some(default.to_string())),
+      (None, None) => Err(TemplateError::Unavailable("latest_release"))
+    },
+    _ => match name.parse::<usize>() {
+      Ok(index) if index > 0 => match (variables.args.get(index - 1), default) {
+        (Some(arg), _) => Ok(Some(arg.clone())),
+        (None, Some(default)) => Ok(Some(default.to_string())),
+        (None, None) => Err(TemplateError::MissingArgument { index, given: variables.args.len() })
+      },
+      _ => Ok(None)
+    }
+  }
+}
+
+/// Splits `args` on whitespace, keeping text in double quotes together.
+pub fn parse_args(args: &str) -> Vec<String> {
+  let mut parsed = Vec::new();
+  let mut current = String::new();
+  let mut quoted = false;
+  let mut started = false;
+
+  for c in args.chars() {
+    match c {
+      '"' => {
+        quoted = !quoted;
+        started = true;
+      },
+      c if c.is_whitespace() && !quoted => {
+        if started {
+          parsed.push(std::mem::take(&mut current));
+          started = false;
+        }
+      },
+      c => {
+        current.push(c);
+        started = true;
+      }
+    }
+  }
+
+  if started {
+    parsed.push(current);
+  }
+
+  parsed
+}
+
+async fn latest_release() -> Option<String> {
+  let result = octocrab::instance()
+    .repos(REPO_OWNER, REPO_NAME)
+    .releases()
+    .get_latest()
+    .await;
+
+  match result {
+    Ok(release) => Some(release.tag_name),
+    Err(e) => {
+      println!("Failed to get the latest release: {e}");
+      None
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn variables(args: &[&str]) -> Variables {
+    Variables::new(UserId(1), ChannelId(2), args.iter().map(|a| a.to_string()).collect())
+  }
+
+  #[test]
+  fn renders_placeholders() {
+    let rendered = render("{user} in {channel}: {1} {2}", &variables(&["a", "b c"]));
+    assert_eq!(rendered.ok().as_deref(), Some("<@1> in <#2>: a b c"));
+  }
+
+  #[test]
+  fn escapes_placeholders() {
+    let rendered = render("{{user}} {{1|x}} {user}", &variables(&[]));
+    assert_eq!(rendered.ok().as_deref(), Some("{user} {1|x} <@1>"));
+  }
+
+  #[test]
+  fn keeps_other_braces() {
+    for text in [r#"{"a": {"b": 1}}"#, "{{a}}", "fn main() {}", "}} {", "{0} {-1} {name}"] {
+      assert_eq!(render(text, &variables(&[])).ok().as_deref(), Some(text));
+    }
+  }
+
+  #[test]
+  fn uses_defaults_for_optional_arguments() {
+    let rendered = render("{1|one} {2|two} {3|}", &variables(&["given"]));
+    assert_eq!(rendered.ok().as_deref(), Some("given two "));
+  }
+
+  #[test]
+  fn fails_on_missing_arguments() {
+    let rendered = render("{1} {3}", &variables(&["a", "b"]));
+    assert!(matches!(rendered, Err(TemplateError::MissingArgument { index: 3, given: 2 })));
+  }
+
+  #[test]
+  fn fails_on_unavailable_release() {
+    let rendered = render("{latest_release}", &variables(&[]));
+    assert!(matches!(rendered, Err(TemplateError::Unavailable("latest_release"))));
+
+    let rendered = render("{latest_release|the latest release}", &variables(&[]));
+    assert_eq!(rendered.ok().as_deref(), Some("the latest release"));
+
+    let mut known = variables(&[]);
+    known.latest_release = Some("v1.0".to_string());
+    assert_eq!(render("{latest_release}", &known).ok().as_deref(), Some("v1.0"));
+  }
+
+  #[test]
+  fn parses_quoted_args() {
+    assert_eq!(parse_args(r#" "Wacom CTL-480"  linux "" "#), vec!["Wacom CTL-480", "linux", ""]);
+  }
+}
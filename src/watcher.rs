@@ -0,0 +1,45 @@
+use serenity::prelude::Context;
+use std::time::Duration;
+use crate::storage::{PersistenceContainer, StorageContainer};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Polls the storage for changes made outside of the bot, replacing the
+/// in-memory state with the changed one if it is valid.
+pub async fn watch(ctx: Context) {
+  let (storage, persistence) = {
+    let data = ctx.data.read().await;
+    let storage = data.get::<StorageContainer>().expect("Failed to get storage").clone();
+    let persistence = data.get::<PersistenceContainer>().expect("Failed to get persistence").clone();
+
+    (storage, persistence)
+  };
+
+  let mut interval = tokio::time::interval(POLL_INTERVAL);
+  let mut last_error = None;
+
+  loop {
+    interval.tick().await;
+
+    match persistence.reload(&ctx.data).await {
+      Some(Ok((snippets, dropped))) => {
+        println!("Reloaded {snippets} snippets from {} storage", storage.name());
+        last_error = None;
+
+        if dropped > 0 {
+          println!("Discarded {dropped} unsaved changes made before the reload");
+        }
+      },
+      Some(Err(e)) => {
+        let error = e.to_string();
+
+        // A broken file is read again every poll, only report it once
+        if last_error.as_ref() != Some(&error) {
+          println!("Refusing to reload invalid state, keeping the current state: {error}");
+          last_error = Some(error);
+        }
+      },
+      None => ()
+    }
+  }
+}
@@ -0,0 +1,12 @@
+CREATE TABLE meta (
+  key TEXT PRIMARY KEY NOT NULL,
+  value TEXT NOT NULL
+);
+CREATE TABLE snippets (
+  id TEXT PRIMARY KEY NOT NULL,
+  data TEXT NOT NULL
+);
+INSERT INTO meta (key, value) VALUES ('version', '1');
+INSERT INTO snippets (id, data) VALUES ('logs', '{"id":"logs","title":"Getting logs","content":"Open the console"}');
+INSERT INTO snippets (id, data) VALUES ('faq', '{"id":"faq","title":"Frequently asked questions","content":"See the wiki"}');
+PRAGMA user_version = 1;
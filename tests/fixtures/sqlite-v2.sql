@@ -0,0 +1,14 @@
+CREATE TABLE meta (
+  key TEXT PRIMARY KEY NOT NULL,
+  value TEXT NOT NULL
+);
+CREATE TABLE snippets (
+  guild_id INTEGER NOT NULL,
+  id TEXT NOT NULL,
+  data TEXT NOT NULL,
+  PRIMARY KEY (guild_id, id)
+);
+INSERT INTO meta (key, value) VALUES ('version', '2');
+INSERT INTO snippets (guild_id, id, data) VALUES (0, 'faq', '{"id":"faq","title":"Frequently asked questions","content":"See the wiki"}');
+INSERT INTO snippets (guild_id, id, data) VALUES (1000, 'rules', '{"id":"rules","title":"Server rules","content":"Be nice"}');
+PRAGMA user_version = 2;
@@ -0,0 +1,33 @@
+CREATE TABLE meta (
+  key TEXT PRIMARY KEY NOT NULL,
+  value TEXT NOT NULL
+);
+CREATE TABLE snippets (
+  guild_id INTEGER NOT NULL,
+  id TEXT NOT NULL,
+  data TEXT NOT NULL,
+  PRIMARY KEY (guild_id, id)
+);
+INSERT INTO meta (key, value) VALUES ('version', '2');
+INSERT INTO snippets (guild_id, id, data) VALUES (0, 'faq', '{"id":"faq","title":"Frequently asked questions","content":"See the wiki"}');
+INSERT INTO snippets (guild_id, id, data) VALUES (1000, 'rules', '{"id":"rules","title":"Server rules","content":"Be nice"}');
+CREATE TABLE audit (
+  timestamp INTEGER NOT NULL,
+  actor INTEGER NOT NULL,
+  guild_id INTEGER NOT NULL,
+  snippet TEXT,
+  data TEXT NOT NULL
+);
+CREATE INDEX audit_guild_timestamp ON audit (guild_id, timestamp);
+INSERT INTO audit (timestamp, actor, guild_id, snippet, data) VALUES (1700000000, 42, 1000, 'rules', '{"timestamp":"2023-11-14T22:13:20Z","actor":"42","guild":"1000","command":"create-snippet","snippet":"rules","before":null,"after":{"id":"rules","title":"Server rules","content":"Be nice"}}');
+CREATE TABLE usage (
+  timestamp INTEGER NOT NULL,
+  user INTEGER NOT NULL,
+  guild_id INTEGER NOT NULL,
+  channel INTEGER NOT NULL,
+  snippet TEXT NOT NULL,
+  source TEXT NOT NULL
+);
+CREATE INDEX usage_timestamp ON usage (timestamp);
+INSERT INTO usage (timestamp, user, guild_id, channel, snippet, source) VALUES (1700000000, 42, 1000, 7, 'rules', 'slash-command');
+PRAGMA user_version = 4;